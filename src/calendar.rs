@@ -0,0 +1,253 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::{
+    read_sessions::{expand_sequence_specification, SequenceNames},
+    types::{Day, Session, WeekNum},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    // days since 1970-01-01 (Howard Hinnant's days_from_civil)
+    fn to_days(self) -> i64 {
+        let year = self.year as i64 - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    fn from_days(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        Date {
+            year: (year_of_era + era * 400 + (month <= 2) as i64) as i32,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+
+    pub fn add_days(self, days: i64) -> Self {
+        Self::from_days(self.to_days() + days)
+    }
+
+    fn is_monday(self) -> bool {
+        // 1970-01-01 was a Thursday
+        (self.to_days() + 3).rem_euclid(7) == 0
+    }
+}
+
+// "2024-09-09"
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad_date = || format!("bad date {s:?}, expected YYYY-MM-DD");
+        let (year, month, day) = s.split('-').collect_tuple().ok_or_else(bad_date)?;
+        let date = Date {
+            year: year.parse().map_err(|_| bad_date())?,
+            month: month.parse().map_err(|_| bad_date())?,
+            day: day.parse().map_err(|_| bad_date())?,
+        };
+
+        // catches things like 2024-02-31
+        if Date::from_days(date.to_days()) != date {
+            return Err(bad_date());
+        }
+
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarFile {
+    week_1_start: String,
+    #[serde(default)]
+    flexibility_week: Option<u8>,
+    #[serde(default)]
+    holidays: Vec<String>,
+    /// e.g. `teaching = "1-5,7-10"`
+    #[serde(default)]
+    week_sets: HashMap<String, String>,
+}
+
+pub struct Calendar {
+    /// the Monday of week 1
+    week_1_start: Date,
+    flexibility_week: Option<WeekNum>,
+    holidays: Vec<Date>,
+    week_sets: SequenceNames,
+}
+
+impl Calendar {
+    pub fn from_toml(input: &str) -> Self {
+        let file: CalendarFile =
+            toml::from_str(input).unwrap_or_else(|err| panic!("bad calendar: {err}"));
+
+        let week_1_start: Date = file
+            .week_1_start
+            .parse()
+            .unwrap_or_else(|err| panic!("{err}"));
+        if !week_1_start.is_monday() {
+            panic!("week 1 should start on a Monday, not {week_1_start}");
+        }
+
+        Calendar {
+            week_1_start,
+            flexibility_week: file.flexibility_week.map(WeekNum),
+            holidays: file
+                .holidays
+                .iter()
+                .map(|holiday| holiday.parse().unwrap_or_else(|err| panic!("{err}")))
+                .collect(),
+            week_sets: file
+                .week_sets
+                .iter()
+                .map(|(name, spec)| {
+                    let weeks = expand_sequence_specification(spec, &SequenceNames::new())
+                        .unwrap_or_else(|err| panic!("{err} in week set {name:?}"));
+                    (name.clone(), weeks)
+                })
+                .collect(),
+        }
+    }
+
+    // the configured week sets, plus "flex" for the flexibility week
+    pub fn week_sets(&self) -> SequenceNames {
+        let mut week_sets = self.week_sets.clone();
+        if let Some(flexibility_week) = self.flexibility_week {
+            week_sets.insert("flex".into(), vec![flexibility_week.0 as i32]);
+        }
+        week_sets
+    }
+
+    pub fn date_of(&self, week: WeekNum, day: Day) -> Date {
+        self.week_1_start
+            .add_days((week.0 as i64 - 1) * 7 + day as i64)
+    }
+
+    pub fn is_flexibility_week(&self, week: WeekNum) -> bool {
+        self.flexibility_week == Some(week)
+    }
+
+    // Dates every session, and drops (and returns) the ones in the flexibility week or on a
+    // holiday.
+    pub fn apply(&self, sessions: &mut Vec<Session>) -> Vec<Session> {
+        let mut dropped = vec![];
+        sessions.retain_mut(|session| {
+            let date = self.date_of(session.week, session.day);
+            session.date = Some(date);
+
+            if self.is_flexibility_week(session.week) {
+                println!("dropping {} (flexibility week)", session.key);
+                dropped.push(session.clone());
+                false
+            } else if self.holidays.contains(&date) {
+                println!("dropping {} (holiday on {date})", session.key);
+                dropped.push(session.clone());
+                false
+            } else {
+                true
+            }
+        });
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        assert_eq!(
+            date("2024-09-09"),
+            Date {
+                year: 2024,
+                month: 9,
+                day: 9
+            }
+        );
+        assert_eq!(date("2024-9-9").to_string(), "2024-09-09");
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for invalid in [
+            "2024-02-30",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-13-01",
+            "2024-00-10",
+            "2024-04-31",
+            "2024-01-00",
+            "2024-01",
+            "2024-01-01-01",
+            "2024/01/01",
+            "",
+        ] {
+            assert!(invalid.parse::<Date>().is_err(), "{invalid:?} parsed");
+        }
+
+        assert!("2024-02-29".parse::<Date>().is_ok());
+        assert!("2000-02-29".parse::<Date>().is_ok());
+    }
+
+    #[test]
+    fn adds_days_across_months_and_years() {
+        assert_eq!(date("2024-02-28").add_days(1), date("2024-02-29"));
+        assert_eq!(date("2023-02-28").add_days(1), date("2023-03-01"));
+        assert_eq!(date("2024-12-31").add_days(1), date("2025-01-01"));
+        assert_eq!(date("2025-01-01").add_days(-1), date("2024-12-31"));
+        assert_eq!(date("2024-09-09").add_days(7 * 10), date("2024-11-18"));
+        assert_eq!(date("1970-01-01").to_days(), 0);
+    }
+
+    #[test]
+    fn mondays() {
+        assert!(date("2024-09-09").is_monday());
+        assert!(date("1970-01-05").is_monday());
+        assert!(!date("2024-09-10").is_monday());
+        assert!(!date("1970-01-01").is_monday());
+    }
+
+    #[test]
+    fn dates_sessions_by_week_and_day() {
+        let calendar = Calendar::from_toml(r#"week_1_start = "2024-09-09""#);
+        assert_eq!(calendar.date_of(WeekNum(1), Day::Mon), date("2024-09-09"));
+        assert_eq!(calendar.date_of(WeekNum(1), Day::Sun), date("2024-09-15"));
+        assert_eq!(calendar.date_of(WeekNum(3), Day::Wed), date("2024-09-25"));
+    }
+}
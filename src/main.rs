@@ -1,63 +1,337 @@
 use std::fs;
 
+use calendar::Calendar;
 use clap::Parser;
-use read_responses::extract_applicants_from_tsv;
+use read_classes::apply_class_timetables;
+use read_pairings::{read_pairings_from_tsv, report_pairings};
+use read_responses::{
+    apply_overrides, availability_questions, blank_responses_tsv, extract_applicants_from_tsv,
+    DuplicatePolicy, MissingAvailability,
+};
+use read_rooms::{assign_rooms, find_room_clashes, read_rooms_from_tsv};
+use read_roster::apply_roster;
 use read_sessions::{
-    apply_priorities, expand_sequence_specification, extract_desired_hours,
-    read_sessions_from_string,
+    apply_priorities, definitions_to_toml, expand_sequence_specification, extract_desired_hours,
+    read_definitions_from_string, read_sessions_from_json, read_sessions_from_string,
+    read_sessions_from_toml, SequenceNames,
 };
 
+use slots::{report_continuity, tabulate_substitutions};
 use solution_output::{
     convert_to_json_output, output_to_atci_toml, tabulate_hours_by_tutor, tabulate_solution_info,
 };
-use solver::solve_many_times;
+use solver::{solve_many_times, SolverOptions, TravelGap};
 use tsv::Tsv;
-use types::Course;
+use types::{Course, Room, Session};
+use verify::find_violations;
 
+mod calendar;
+mod read_classes;
+mod read_pairings;
 mod read_responses;
+mod read_rooms;
+mod read_roster;
 mod read_sessions;
+mod slots;
 mod solution_output;
 mod solver;
 mod tsv;
 mod types;
+mod verify;
 
 #[derive(clap::Parser, Debug)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    course: Course,
-    seed: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(required = true)]
+    course: Option<Course>,
+    #[arg(required = true)]
+    seed: Option<String>,
     #[arg(long)]
     no_write: bool,
     #[arg(long)]
     quick: bool,
+    /// Which form submission to keep for tutors who submitted more than once
+    #[arg(long, value_enum, default_value_t = DuplicatePolicy::Latest)]
+    duplicates: DuplicatePolicy,
+    /// Availability to assume for sessions with no question on the form
+    #[arg(long, value_enum, default_value_t = MissingAvailability::Impossible)]
+    missing_availability: MissingAvailability,
+    /// Extra cost of an in-person session back-to-back with a tutor's class on another campus
+    #[arg(long)]
+    campus_change_penalty: Option<u64>,
+    /// Most hours a tutor works in a day, unless their form or roster row says otherwise
+    #[arg(long)]
+    max_hours_per_day: Option<u16>,
+    /// Longest run of back-to-back hours, unless their form or roster row says otherwise
+    #[arg(long)]
+    max_consecutive_hours: Option<u16>,
+    /// Minutes a tutor needs between sessions on a day when the venue or campus changes
+    #[arg(long)]
+    travel_gap: Option<u16>,
+    /// Make a short travel gap cost this much, rather than be impossible
+    #[arg(long, requires = "travel_gap")]
+    travel_gap_penalty: Option<u64>,
+    /// Cost of each day a tutor comes in to campus, to bunch in-person sessions onto fewer days
+    /// (e.g. 20; off by default)
+    #[arg(long, default_value_t = 0)]
+    campus_day_cost: u64,
+    /// Cost per hour a tutor waits on campus between in-person sessions on a day, to favour
+    /// back-to-back ones (e.g. 5; off by default)
+    #[arg(long, default_value_t = 0)]
+    campus_idle_hour_cost: u64,
+    /// Most days a week a tutor comes in to campus
+    #[arg(long)]
+    max_campus_days: Option<u8>,
+    /// Cost of each tutor who joins or leaves a recurring slot from one week to the next
+    /// (e.g. 5; off by default)
+    #[arg(long, default_value_t = 0)]
+    slot_change_cost: u64,
+    /// Give tutors recurring slots for the whole term, then find substitutes for gaps
+    #[arg(long)]
+    whole_term: bool,
+    /// Cost per square hour of variance in a tutor's weekly hours (tutors tagged bursty on the
+    /// roster are exempt; e.g. 5, off by default)
+    #[arg(long, default_value_t = 0)]
+    hours_variance_cost: u64,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Write the form's session questions and a blank responses TSV from the session definitions
+    FormTemplate,
+    /// Convert sessions.txt into sessions.toml
+    ConvertSessions {
+        /// Overwrite an existing sessions.toml
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check for rooms double-booked by these sessions and other courses' session files
+    CheckRooms { other_session_files: Vec<String> },
+}
+
+fn read_calendar() -> Option<Calendar> {
+    let contents = fs::read_to_string("calendar.toml").ok()?;
+    Some(Calendar::from_toml(&contents))
+}
+
+fn week_sets(calendar: Option<&Calendar>) -> SequenceNames {
+    calendar.map_or_else(SequenceNames::new, Calendar::week_sets)
+}
+
+fn read_rooms() -> Option<Vec<Room>> {
+    let contents = fs::read_to_string("rooms.tsv").ok()?;
+    Some(read_rooms_from_tsv(&Tsv::from_string(&contents)))
+}
+
+// The format is picked by extension, defaulting to the sessions.txt format. Also returns the
+// sessions the calendar dropped.
+fn read_session_file(
+    path: &str,
+    course: Option<Course>,
+    calendar: Option<&Calendar>,
+) -> (Vec<Session>, Vec<Session>) {
+    let week_sets = week_sets(calendar);
+    let contents = fs::read_to_string(path).unwrap_or_else(|err| panic!("{path}: {err}"));
+    let mut sessions = if path.ends_with(".toml") {
+        read_sessions_from_toml(&contents, course, &week_sets)
+    } else if path.ends_with(".json") {
+        read_sessions_from_json(&contents, course, &week_sets)
+    } else {
+        read_sessions_from_string(&contents, &week_sets)
+    };
+
+    let dropped = calendar.map_or_else(Vec::new, |calendar| calendar.apply(&mut sessions));
+
+    if let Some(rooms) = read_rooms() {
+        assign_rooms(&rooms, &mut sessions);
+    }
+
+    (sessions, dropped)
+}
+
+// sessions.toml or sessions.json if present, otherwise sessions.txt
+fn read_sessions(
+    course: Option<Course>,
+    calendar: Option<&Calendar>,
+) -> (Vec<Session>, Vec<Session>) {
+    let path = ["sessions.toml", "sessions.json"]
+        .into_iter()
+        .find(|path| fs::metadata(path).is_ok())
+        .unwrap_or("sessions.txt");
+    if path != "sessions.txt" {
+        println!("reading sessions from {path}");
+    }
+
+    read_session_file(path, course, calendar)
+}
+
+// Sessions are paired with where they came from. Prints every clash, then panics if there were
+// any. Identical parallel sessions from the same file share their room on purpose, so copies of
+// one session don't clash with each other.
+fn check_room_clashes(sessions: &[(&str, &Session)]) {
+    let mut clashes = find_room_clashes(
+        &sessions
+            .iter()
+            .map(|&(_, session)| session)
+            .collect::<Vec<_>>(),
+    );
+    clashes.retain(|&(first, second)| {
+        let (first_source, first) = sessions[first];
+        let (second_source, second) = sessions[second];
+        let copies = first_source == second_source
+            && first.copy != second.copy
+            && first.definition_key() == second.definition_key();
+        !copies
+    });
+
+    for &(first, second) in &clashes {
+        let (first_source, first) = &sessions[first];
+        let (second_source, second) = &sessions[second];
+        println!(
+            "room clash in {}: {} ({first_source}) and {} ({second_source})",
+            first.location, first.key, second.key
+        );
+    }
+
+    if !clashes.is_empty() {
+        panic!("{} room clashes", clashes.len());
+    }
+}
+
+fn check_rooms(other_session_files: &[String]) {
+    if read_rooms().is_none() {
+        panic!("no rooms.tsv to check against");
+    }
+
+    let calendar = read_calendar();
+    let (own_sessions, _) = read_sessions(None, calendar.as_ref());
+    let other_sessions = other_session_files
+        .iter()
+        .map(|path| (path, read_session_file(path, None, calendar.as_ref()).0))
+        .collect::<Vec<_>>();
+
+    let mut sessions = own_sessions
+        .iter()
+        .map(|session| ("these sessions", session))
+        .collect::<Vec<_>>();
+    for (path, other) in &other_sessions {
+        sessions.extend(other.iter().map(|session| (path.as_str(), session)));
+    }
+
+    check_room_clashes(&sessions);
+    println!("no room clashes in {} sessions", sessions.len());
+}
+
+fn convert_sessions(force: bool) {
+    // sessions.toml is read in preference to sessions.txt, and may have been edited by hand
+    if !force && fs::metadata("sessions.toml").is_ok() {
+        panic!("sessions.toml already exists (use --force to overwrite it)");
+    }
+
+    let definitions = read_definitions_from_string(&fs::read_to_string("sessions.txt").unwrap());
+    println!("converted {} session lines", definitions.len());
+
+    fs::write("sessions.toml", definitions_to_toml(definitions)).unwrap();
+}
+
+fn write_form_template() {
+    let (sessions, _) = read_sessions(None, read_calendar().as_ref());
+
+    let questions = availability_questions(&sessions);
+    println!("{} availability questions", questions.len());
+
+    fs::write("form_questions.txt", questions.join("\n") + "\n").unwrap();
+    // not responses.tsv itself, so real responses are never clobbered
+    fs::write("responses.blank.tsv", blank_responses_tsv(&sessions)).unwrap();
 }
 
 fn main() {
     let args = Args::parse();
-    let course = args.course;
+
+    match &args.command {
+        Some(Command::FormTemplate) => return write_form_template(),
+        Some(Command::ConvertSessions { force }) => return convert_sessions(*force),
+        Some(Command::CheckRooms {
+            other_session_files,
+        }) => return check_rooms(other_session_files),
+        None => {}
+    }
+
+    let course = args.course.unwrap();
 
     println!("{}", "=".repeat(80));
     println!("{:?}", args);
     println!("{}", "-".repeat(80));
 
+    let calendar = read_calendar();
+    let week_sets = week_sets(calendar.as_ref());
+
     let sessions = {
-        let mut sessions = read_sessions_from_string(&fs::read_to_string("sessions.txt").unwrap());
+        let (mut sessions, dropped) = read_sessions(Some(course), calendar.as_ref());
         let priorities = Tsv::from_string(&fs::read_to_string("priorities.tsv").unwrap());
-        apply_priorities(args.course, &priorities, &mut sessions);
+        apply_priorities(course, &priorities, &mut sessions, &dropped, &week_sets);
         sessions
     };
 
     println!("{} sessions to schedule", sessions.len());
 
+    let course_name = course.to_string();
+    check_room_clashes(
+        &sessions
+            .iter()
+            .map(|session| (course_name.as_str(), session))
+            .collect::<Vec<_>>(),
+    );
+
     let responses = Tsv::from_string(&fs::read_to_string("responses.tsv").unwrap());
     println!("{} form responses", responses.num_rows());
 
     let desired_hours_tsv = Tsv::from_string(&fs::read_to_string("desired_hours.tsv").unwrap());
-    let desired_hours = extract_desired_hours(desired_hours_tsv, course);
+    let mut desired_hours = extract_desired_hours(desired_hours_tsv, course);
+    if let Some(calendar) = &calendar {
+        desired_hours.retain(|&(week, _)| !calendar.is_flexibility_week(week));
+    }
+
+    let mut applicants = extract_applicants_from_tsv(
+        responses,
+        &sessions,
+        args.duplicates,
+        args.missing_availability,
+    );
+
+    if let Ok(roster) = fs::read_to_string("roster.tsv") {
+        apply_roster(&Tsv::from_string(&roster), &mut applicants);
+    }
+
+    if let Ok(classes) = fs::read_to_string("classes.tsv") {
+        apply_class_timetables(
+            &Tsv::from_string(&classes),
+            &sessions,
+            &mut applicants,
+            &week_sets,
+            read_rooms().as_deref(),
+            args.campus_change_penalty,
+        );
+    }
 
-    let applicants = extract_applicants_from_tsv(responses, &sessions);
+    if let Ok(overrides) = fs::read_to_string("overrides.tsv") {
+        apply_overrides(
+            &Tsv::from_string(&overrides),
+            &sessions,
+            &mut applicants,
+            &week_sets,
+        );
+    }
+
+    let pairings = fs::read_to_string("pairings.tsv")
+        .map(|pairings| read_pairings_from_tsv(&Tsv::from_string(&pairings), &applicants))
+        .unwrap_or_default();
 
-    let (solution, best_seed) = solve_many_times(
-        expand_sequence_specification(&args.seed)
+    let (solution, substitutions, best_seed) = solve_many_times(
+        expand_sequence_specification(args.seed.as_deref().unwrap(), &week_sets)
+            .unwrap_or_else(|err| panic!("bad seeds: {err}"))
             .into_iter()
             .map(|seed| seed as u64)
             .collect(),
@@ -65,32 +339,72 @@ fn main() {
         &applicants,
         &sessions,
         &desired_hours,
-        args.quick,
+        &pairings,
+        &SolverOptions {
+            quick: args.quick,
+            max_hours_per_day: args.max_hours_per_day,
+            max_consecutive_hours: args.max_consecutive_hours,
+            travel_gap: args.travel_gap.map(|minutes| TravelGap {
+                minutes,
+                penalty: args.travel_gap_penalty,
+            }),
+            campus_day_cost: args.campus_day_cost,
+            campus_idle_hour_cost: args.campus_idle_hour_cost,
+            max_campus_days: args.max_campus_days,
+            slot_change_cost: args.slot_change_cost,
+            whole_term: args.whole_term,
+            hours_variance_cost: args.hours_variance_cost,
+        },
     );
 
+    for violation in find_violations(&solution) {
+        println!("violation: {violation}");
+    }
+
+    for pairing in report_pairings(&pairings, &solution) {
+        println!("pairing: {pairing}");
+    }
+
+    for continuity in report_continuity(&solution) {
+        println!("continuity: {continuity}");
+    }
+
+    for substitution in &substitutions {
+        println!(
+            "substitute: {} for {} in {}",
+            substitution.substitute_zid.as_deref().unwrap_or("no one"),
+            substitution.absent_zid,
+            substitution.session_key
+        );
+    }
+
     let solution_info = tabulate_solution_info(solution.clone());
 
     if !args.no_write {
-        fs::write(
-            format!("solution.{}.tsv", course.to_string()),
-            solution_info,
-        )
-        .unwrap();
+        if args.whole_term {
+            fs::write(
+                format!("substitutes.{}.tsv", course),
+                tabulate_substitutions(&substitutions),
+            )
+            .unwrap();
+        }
+
+        fs::write(format!("solution.{}.tsv", course), solution_info).unwrap();
 
         fs::write(
-            format!("hours.{}.tsv", course.to_string()),
+            format!("hours.{}.tsv", course),
             tabulate_hours_by_tutor(solution.clone()),
         )
         .unwrap();
 
         fs::write(
-            format!("help_sessions.{}.toml", course.to_string()),
+            format!("help_sessions.{}.toml", course),
             output_to_atci_toml(solution.clone(), best_seed),
         )
         .unwrap();
 
         fs::write(
-            format!("help_sessions.{}.json", course.to_string()),
+            format!("help_sessions.{}.json", course),
             convert_to_json_output(solution.clone(), best_seed, course),
         )
         .unwrap();
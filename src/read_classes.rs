@@ -0,0 +1,89 @@
+use crate::{
+    read_rooms::find_room,
+    read_sessions::{
+        expand_sequence_specification, parse_24_hour_time, parse_length, SequenceNames,
+    },
+    solver::Cost,
+    tsv::Tsv,
+    types::{Applicant, Availability, Day, Room, Session},
+};
+
+/// Help sessions starting or ending within this long of a class count as back-to-back with it
+const BACK_TO_BACK_MINUTES: u16 = 15;
+
+// classes.tsv has a row per class a tutor teaches: zID, Week, Day, Start, Length (like
+// sessions.txt, e.g. "2hrs" or "90mins") and Location, plus an optional Campus (otherwise looked
+// up from rooms.tsv).
+//
+// Help sessions overlapping a class become Impossible. With a campus change penalty, in-person
+// help sessions back-to-back with a class on another campus cost that much extra.
+pub fn apply_class_timetables(
+    classes: &Tsv,
+    sessions: &[Session],
+    applicants: &mut [Applicant],
+    names: &SequenceNames,
+    rooms: Option<&[Room]>,
+    campus_change_penalty: Option<Cost>,
+) {
+    for row in classes {
+        let zid = row.get("zID");
+        let Some(applicant) = applicants.iter_mut().find(|applicant| applicant.zid == zid) else {
+            println!("warning: {zid} has a class but didn't fill in the form");
+            continue;
+        };
+
+        let bad_row = || format!("in class {:?}", row.all_fields());
+        let weeks = expand_sequence_specification(row.get("Week"), names)
+            .unwrap_or_else(|err| panic!("{err} {}", bad_row()));
+        let day: Day = row
+            .get("Day")
+            .parse()
+            .unwrap_or_else(|err| panic!("{err} {}", bad_row()));
+        let start = parse_24_hour_time(row.get("Start"))
+            .unwrap_or_else(|| panic!("bad start time {}", bad_row()));
+        let end = start
+            + parse_length(row.get("Length")).unwrap_or_else(|| panic!("bad length {}", bad_row()));
+        let campus = row
+            .try_get("Campus")
+            .filter(|campus| !campus.is_empty())
+            .map(String::from)
+            .or_else(|| find_room(rooms?, row.get("Location")).map(|room| room.campus.clone()));
+
+        let mut num_clashes = 0;
+        let mut num_back_to_back = 0;
+
+        for (session_index, session) in sessions.iter().enumerate() {
+            if !weeks.contains(&(session.week.0 as i32)) || session.day != day {
+                continue;
+            }
+
+            if session.start_minutes < end && start < session.end_minutes() {
+                if applicant.availabilities[session_index] != Availability::Impossible {
+                    applicant.availabilities[session_index] = Availability::Impossible;
+                    num_clashes += 1;
+                }
+                continue;
+            }
+
+            let (Some(penalty), Some(class_campus), Some(room)) =
+                (campus_change_penalty, &campus, &session.room)
+            else {
+                continue;
+            };
+            let back_to_back = session.end_minutes().abs_diff(start) <= BACK_TO_BACK_MINUTES
+                || end.abs_diff(session.start_minutes) <= BACK_TO_BACK_MINUTES;
+
+            if back_to_back && room.campus != *class_campus {
+                applicant.extra_costs[session_index] += penalty;
+                num_back_to_back += 1;
+            }
+        }
+
+        println!(
+            "class for {zid} ({} {}): {num_clashes} sessions now Impossible, \
+             {num_back_to_back} back-to-back on another campus",
+            day.short_name(),
+            row.get("Week"),
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    solution_output::SolvedSession,
+    tsv::Tsv,
+    types::{Applicant, Pairing, PairingKind},
+};
+
+const DEFAULT_PREFER_TOGETHER_WEIGHT: u16 = 10;
+
+// pairings.tsv: "zID 1", "zID 2", "Preference" ("prefer together" or "never together") and
+// "Weight". A blank weight makes "never together" a hard constraint.
+//
+// Pairs with someone teaching another course are ignored when solving, but a zID that's not on the
+// form at all is probably a typo, so it gets a warning and the pair is dropped.
+pub fn read_pairings_from_tsv(tsv: &Tsv, applicants: &[Applicant]) -> Vec<Pairing> {
+    tsv.into_iter()
+        .filter_map(|row| {
+            let unknown = [row.get("zID 1"), row.get("zID 2")]
+                .into_iter()
+                .filter(|&zid| !applicants.iter().any(|applicant| applicant.zid == zid))
+                .collect::<Vec<_>>();
+            if !unknown.is_empty() {
+                println!(
+                    "warning: ignoring pairing {:?}, {} didn't fill in the form",
+                    row.all_fields(),
+                    unknown.join(" and ")
+                );
+                return None;
+            }
+
+            let kind = match row.get("Preference").trim().to_lowercase().as_str() {
+                "prefer together" => PairingKind::PreferTogether,
+                "never together" => PairingKind::NeverTogether,
+                other => panic!("bad pairing preference {other:?}"),
+            };
+            let weight = match row.get("Weight").trim() {
+                "" if kind == PairingKind::PreferTogether => Some(DEFAULT_PREFER_TOGETHER_WEIGHT),
+                "" => None,
+                weight => Some(
+                    weight
+                        .parse()
+                        .unwrap_or_else(|_| panic!("bad weight in pairing {:?}", row.all_fields())),
+                ),
+            };
+
+            Some(Pairing {
+                zids: (row.get("zID 1").into(), row.get("zID 2").into()),
+                kind,
+                weight,
+            })
+        })
+        .collect()
+}
+
+// One line per pairing, saying how often the pair worked together
+pub fn report_pairings(pairings: &[Pairing], solution: &[SolvedSession]) -> Vec<String> {
+    pairings
+        .iter()
+        .map(|pairing| {
+            let (first, second) = &pairing.zids;
+            let works = |solved: &SolvedSession, zid: &str| {
+                solved
+                    .applicants
+                    .iter()
+                    .any(|applicant| applicant.zid == zid)
+            };
+
+            let together = solution
+                .iter()
+                .filter(|solved| works(solved, first) && works(solved, second))
+                .count();
+            let either = solution
+                .iter()
+                .filter(|solved| works(solved, first) || works(solved, second))
+                .count();
+
+            let outcome = match pairing.kind {
+                PairingKind::PreferTogether if together == either => "satisfied",
+                PairingKind::NeverTogether if together == 0 => "satisfied",
+                _ => "not satisfied",
+            };
+
+            format!(
+                "{first} & {second} ({:?}): {outcome}, together in {together} of {either} sessions",
+                pairing.kind
+            )
+        })
+        .collect()
+}
@@ -1,10 +1,37 @@
 use std::str::FromStr;
 
+use itertools::Itertools;
+
 use crate::{
-    tsv::Tsv,
-    types::{Applicant, Availability, Session, Venue},
+    read_sessions::{expand_sequence_specification, parse_24_hour_time, SequenceNames},
+    tsv::{Row, Tsv},
+    types::{Applicant, Availability, Day, Session, Venue, VenueSpec},
 };
 
+/// Which submission to keep when someone fills in the form more than once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DuplicatePolicy {
+    Latest,
+    Earliest,
+}
+
+/// What to assume for sessions that have no question on the form (e.g. added after it went out).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum MissingAvailability {
+    Impossible,
+    /// Schedulable, but only as a last resort (treated like Dislike)
+    Unknown,
+}
+
+impl MissingAvailability {
+    fn availability(self) -> Availability {
+        match self {
+            MissingAvailability::Impossible => Availability::Impossible,
+            MissingAvailability::Unknown => Availability::Dislike,
+        }
+    }
+}
+
 impl FromStr for Availability {
     type Err = ();
 
@@ -19,29 +46,170 @@ impl FromStr for Availability {
     }
 }
 
-fn twentfour_hour_to_twelve_hour(time: u8) -> String {
-    #[allow(clippy::comparison_chain)]
-    if time == 12 {
-        String::from("12pm")
-    } else if time < 12 {
-        format!("{time}am")
+const COURSE_QUESTION: &str = "Which course are you primarily teaching?";
+const HOURS_QUESTION: &str =
+    "Around how many hours would you like to work on help sessions, per week?";
+const UNAVAILABLE_WEEKS_QUESTION: &str = "Are then any weeks you specifically are not available?";
+// optional, in both responses.tsv and roster.tsv
+pub const MAX_HOURS_PER_DAY_COLUMN: &str = "Max hours per day";
+pub const MAX_CONSECUTIVE_HOURS_COLUMN: &str = "Max consecutive hours";
+
+// every column in responses.tsv that isn't a session availability
+const NON_SESSION_COLUMNS: [&str; 9] = [
+    "Timestamp",
+    "Email",
+    "Name",
+    COURSE_QUESTION,
+    HOURS_QUESTION,
+    "Min hours",
+    MAX_HOURS_PER_DAY_COLUMN,
+    MAX_CONSECUTIVE_HOURS_COLUMN,
+    UNAVAILABLE_WEEKS_QUESTION,
+];
+
+// minutes after midnight ==> "9am" or "1:30pm"
+fn minutes_to_twelve_hour(time: u16) -> String {
+    let (hour, minute) = (time / 60, time % 60);
+    let suffix = if (12..24).contains(&hour) { "pm" } else { "am" };
+    let hour = match hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+
+    if minute == 0 {
+        format!("{hour}{suffix}")
     } else {
-        format!("{}pm", time - 12)
+        format!("{hour}:{minute:02}{suffix}")
+    }
+}
+
+// the form question (and so the responses.tsv column) asking about this session
+pub fn availability_column_name(session: &Session) -> String {
+    format!(
+        "{}{} {}-{}",
+        match session.venue {
+            Venue::FaceToFace => "",
+            Venue::Online => "Online ",
+            Venue::Hybrid => "Hybrid ",
+        },
+        session.day.long_name(),
+        minutes_to_twelve_hour(session.start_minutes),
+        minutes_to_twelve_hour(session.end_minutes()),
+    )
+}
+
+// Each recurring slot is asked about once, in the order it first appears in sessions.txt
+pub fn availability_questions(sessions: &[Session]) -> Vec<String> {
+    sessions
+        .iter()
+        .map(availability_column_name)
+        .unique()
+        .collect()
+}
+
+pub fn blank_responses_tsv(sessions: &[Session]) -> String {
+    let mut header = NON_SESSION_COLUMNS.map(String::from).to_vec();
+    header.extend(availability_questions(sessions));
+    header.join("\t") + "\n"
+}
+
+// "20/05/2024 14:03:11" ==> (2024, 5, 20, 14, 3, 11)
+// (the form is owned by an en-AU account, so dates are day first)
+fn parse_form_timestamp(s: &str) -> Option<(u16, u8, u8, u8, u8, u8)> {
+    let (date, time) = s.trim().split_once(' ')?;
+    let (day, month, year) = date.split('/').collect_tuple()?;
+    let (hour, minute, second) = time.split(':').collect_tuple()?;
+    Some((
+        year.parse().ok()?,
+        month.parse().ok()?,
+        day.parse().ok()?,
+        hour.parse().ok()?,
+        minute.parse().ok()?,
+        second.parse().ok()?,
+    ))
+}
+
+fn deduplicate_submissions(tsv: &Tsv, policy: DuplicatePolicy) -> Vec<Row<'_>> {
+    let mut kept: Vec<Row> = vec![];
+
+    for row in tsv {
+        let email = row.get("Email");
+        let timestamp = parse_form_timestamp(row.get("Timestamp"))
+            .unwrap_or_else(|| panic!("bad timestamp {:?} for {email:?}", row.get("Timestamp")));
+
+        let Some(existing) = kept
+            .iter()
+            .position(|kept_row| kept_row.get("Email").eq_ignore_ascii_case(email))
+        else {
+            kept.push(row);
+            continue;
+        };
+
+        let existing_timestamp = parse_form_timestamp(kept[existing].get("Timestamp")).unwrap();
+        let replace = match policy {
+            DuplicatePolicy::Latest => timestamp >= existing_timestamp,
+            DuplicatePolicy::Earliest => timestamp < existing_timestamp,
+        };
+
+        let (keeping, discarding) = if replace {
+            (&row, &kept[existing])
+        } else {
+            (&kept[existing], &row)
+        };
+        println!(
+            "merged duplicate submission from {email}: keeping {:?}, discarding {:?}",
+            keeping.get("Timestamp"),
+            discarding.get("Timestamp"),
+        );
+
+        if replace {
+            kept[existing] = row;
+        }
     }
+
+    kept
 }
 
-pub fn extract_applicants_from_tsv(tsv: Tsv, sessions: &[Session]) -> Vec<Applicant> {
-    (&tsv)
+pub fn extract_applicants_from_tsv(
+    tsv: Tsv,
+    sessions: &[Session],
+    duplicates: DuplicatePolicy,
+    missing: MissingAvailability,
+) -> Vec<Applicant> {
+    let missing_columns = sessions
+        .iter()
+        .map(|session| !tsv.has_column(&availability_column_name(session)))
+        .collect::<Vec<_>>();
+
+    let sessions_without_questions = sessions
+        .iter()
+        .zip(&missing_columns)
+        .filter(|(_, &missing)| missing)
+        .map(|(session, _)| session)
+        .collect::<Vec<_>>();
+    if !sessions_without_questions.is_empty() {
+        println!(
+            "warning: {} sessions have no question on the form, treating as {missing:?}:",
+            sessions_without_questions.len()
+        );
+        for session in sessions_without_questions {
+            println!(
+                "    {} {:?}",
+                session.key,
+                availability_column_name(session)
+            );
+        }
+    }
+
+    deduplicate_submissions(&tsv, duplicates)
         .into_iter()
-        .enumerate()
-        .map(|(idx, row)| {
+        .map(|row| {
             let email = row.get("Email");
             let zid = email.strip_suffix("@ad.unsw.edu.au").unwrap();
             let name = row.get("Name");
-            let course_raw = row.get("Which course are you primarily teaching?");
+            let course_raw = row.get(COURSE_QUESTION);
             let course = course_raw.parse().unwrap();
-            let raw_hours_request =
-                row.get("Around how many hours would you like to work on help sessions, per week?");
+            let raw_hours_request = row.get(HOURS_QUESTION);
             let max_hours_per_week = match raw_hours_request {
                 "1-5" => 5,
                 "6-10" => 10,
@@ -54,8 +222,10 @@ pub fn extract_applicants_from_tsv(tsv: Tsv, sessions: &[Session]) -> Vec<Applic
             } else {
                 Some(raw_min_hours.parse().unwrap())
             };
+            let max_hours_per_day = optional_hours(&row, MAX_HOURS_PER_DAY_COLUMN);
+            let max_consecutive_hours = optional_hours(&row, MAX_CONSECUTIVE_HOURS_COLUMN);
             let cant_do_weeks = row
-                .get("Are then any weeks you specifically are not available?")
+                .get(UNAVAILABLE_WEEKS_QUESTION)
                 .split(';')
                 .filter(|s| !s.is_empty())
                 .map(|week| {
@@ -68,23 +238,17 @@ pub fn extract_applicants_from_tsv(tsv: Tsv, sessions: &[Session]) -> Vec<Applic
 
             let availabilities = sessions
                 .iter()
-                .map(|session| {
+                .zip(&missing_columns)
+                .map(|(session, &missing_column)| {
                     if cant_do_weeks.contains(&session.week.0) {
                         return Availability::Impossible;
                     }
 
-                    let column_name = format!(
-                        "{}{} {}-{}",
-                        match session.venue {
-                            Venue::FaceToFace => "",
-                            Venue::Online => "Online ",
-                        },
-                        session.day.long_name(),
-                        twentfour_hour_to_twelve_hour(session.time_24hr),
-                        twentfour_hour_to_twelve_hour(session.time_24hr + session.length_hours),
-                    );
-
-                    let raw_availability = row.get(&column_name);
+                    if missing_column {
+                        return missing.availability();
+                    }
+
+                    let raw_availability = row.get(&availability_column_name(session));
                     raw_availability
                         .parse()
                         .unwrap_or_else(|()| panic!("bad availability {raw_availability:?}"))
@@ -92,15 +256,120 @@ pub fn extract_applicants_from_tsv(tsv: Tsv, sessions: &[Session]) -> Vec<Applic
                 .collect();
 
             Applicant {
-                id: idx as _,
                 email: email.into(),
                 zid: zid.into(),
                 name: name.into(),
                 course,
                 max_hours_per_week,
                 availabilities,
+                extra_costs: vec![0; sessions.len()],
                 min_hours_per_week: min_hours,
+                max_hours_per_day,
+                max_consecutive_hours,
+                tags: vec![],
             }
         })
         .collect()
 }
+
+// a blank or missing column is None
+pub fn optional_hours(row: &Row, column: &str) -> Option<u16> {
+    let hours = row.try_get(column).filter(|hours| !hours.is_empty())?;
+    Some(
+        hours
+            .parse()
+            .unwrap_or_else(|_| panic!("bad {column:?} {hours:?} in {:?}", row.all_fields())),
+    )
+}
+
+// a blank or missing column is None
+fn optional_field<'a>(row: &Row<'a>, column: &str) -> Option<&'a str> {
+    row.try_get(column).filter(|field| !field.is_empty())
+}
+
+// Blank or missing fields in an override row match anything (or leave the value alone).
+// Overrides for someone who didn't fill in the form are skipped with a warning.
+pub fn apply_overrides(
+    overrides: &Tsv,
+    sessions: &[Session],
+    applicants: &mut [Applicant],
+    names: &SequenceNames,
+) {
+    for row in overrides {
+        let zid = row.get("zID");
+        let Some(applicant) = applicants.iter_mut().find(|applicant| applicant.zid == zid) else {
+            println!("warning: {zid} has an override but didn't fill in the form");
+            continue;
+        };
+
+        let weeks = optional_field(&row, "Weeks").map(|weeks| {
+            expand_sequence_specification(weeks, names)
+                .unwrap_or_else(|err| panic!("{err} in override {:?}", row.all_fields()))
+        });
+        let day: Option<Day> = optional_field(&row, "Day").map(|day| {
+            day.parse()
+                .unwrap_or_else(|err| panic!("{err} in override {:?}", row.all_fields()))
+        });
+        let from = optional_field(&row, "From").map_or(0, |from| {
+            parse_24_hour_time(from)
+                .unwrap_or_else(|| panic!("bad time {from:?} in override {:?}", row.all_fields()))
+        });
+        let until = optional_field(&row, "Until").map_or(24 * 60, |until| {
+            parse_24_hour_time(until)
+                .unwrap_or_else(|| panic!("bad time {until:?} in override {:?}", row.all_fields()))
+        });
+        let venue: Option<VenueSpec> = optional_field(&row, "Venue").map(|venue| {
+            venue
+                .parse()
+                .unwrap_or_else(|err| panic!("{err} in override {:?}", row.all_fields()))
+        });
+        let availability: Option<Availability> =
+            optional_field(&row, "Availability").map(|availability| {
+                availability.parse().unwrap_or_else(|()| {
+                    panic!(
+                        "bad availability {availability:?} in override {:?}",
+                        row.all_fields()
+                    )
+                })
+            });
+
+        let mut changes = vec![];
+
+        if let Some(availability) = availability {
+            let mut num_changed = 0;
+            for (session, current) in sessions.iter().zip(&mut applicant.availabilities) {
+                let matches = weeks
+                    .as_ref()
+                    .is_none_or(|weeks| weeks.contains(&(session.week.0 as i32)))
+                    && day.is_none_or(|day| session.day == day)
+                    && venue.as_ref().is_none_or(|venue| venue.matches(session))
+                    && session.start_minutes < until
+                    && session.end_minutes() > from;
+
+                if matches && *current != availability {
+                    *current = availability;
+                    num_changed += 1;
+                }
+            }
+            changes.push(format!("{num_changed} sessions set to {availability:?}"));
+        }
+
+        if let Some(max_hours) = optional_hours(&row, "Max hours") {
+            changes.push(format!(
+                "max hours {} -> {max_hours}",
+                applicant.max_hours_per_week
+            ));
+            applicant.max_hours_per_week = max_hours;
+        }
+
+        if let Some(min_hours) = optional_hours(&row, "Min hours") {
+            changes.push(format!(
+                "min hours {:?} -> {min_hours}",
+                applicant.min_hours_per_week
+            ));
+            applicant.min_hours_per_week = Some(min_hours);
+        }
+
+        println!("override for {zid}: {}", changes.join(", "));
+    }
+}
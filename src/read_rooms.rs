@@ -0,0 +1,86 @@
+use crate::{
+    tsv::Tsv,
+    types::{Room, Session},
+};
+
+// sessions.txt writes spaces in locations as underscores
+fn normalise_room_id(id: &str) -> String {
+    id.trim().replace('_', " ")
+}
+
+pub fn find_room<'a>(rooms: &'a [Room], id: &str) -> Option<&'a Room> {
+    let id = normalise_room_id(id);
+    rooms.iter().find(|room| room.id == id)
+}
+
+pub fn read_rooms_from_tsv(tsv: &Tsv) -> Vec<Room> {
+    tsv.into_iter()
+        .map(|row| Room {
+            id: normalise_room_id(row.get("Room")),
+            capacity: row
+                .get("Capacity")
+                .parse()
+                .unwrap_or_else(|_| panic!("bad capacity for room {:?}", row.all_fields())),
+            campus: row.get("Campus").into(),
+            flags: row
+                .try_get("Flags")
+                .unwrap_or_default()
+                .split(';')
+                .map(str::trim)
+                .filter(|flag| !flag.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+        .collect()
+}
+
+// Every in-person (face-to-face or hybrid) session's location has to be a room ID from rooms.tsv.
+pub fn assign_rooms(rooms: &[Room], sessions: &mut [Session]) {
+    for session in sessions {
+        if !session.venue.is_in_person() {
+            continue;
+        }
+
+        let room = find_room(rooms, &session.location)
+            .unwrap_or_else(|| panic!("{} is in unknown room {:?}", session.key, session.location));
+
+        for flag in &session.room_flags {
+            if !room.flags.contains(flag) {
+                println!(
+                    "warning: {} needs a room with {flag:?}, but {} doesn't have it",
+                    session.key, room.id
+                );
+            }
+        }
+
+        session.room = Some(room.clone());
+    }
+}
+
+// Pairs of sessions (by index) booked into the same room at overlapping times
+pub fn find_room_clashes(sessions: &[&Session]) -> Vec<(usize, usize)> {
+    let mut clashes = vec![];
+
+    for (first_index, first) in sessions.iter().enumerate() {
+        let Some(first_room) = &first.room else {
+            continue;
+        };
+
+        for (second_index, second) in sessions.iter().enumerate().skip(first_index + 1) {
+            let same_room = second
+                .room
+                .as_ref()
+                .is_some_and(|second_room| second_room.id == first_room.id);
+            let overlapping = first.week == second.week
+                && first.day == second.day
+                && first.start_minutes < second.end_minutes()
+                && second.start_minutes < first.end_minutes();
+
+            if same_room && overlapping {
+                clashes.push((first_index, second_index));
+            }
+        }
+    }
+
+    clashes
+}
@@ -0,0 +1,37 @@
+use crate::{
+    read_responses::{optional_hours, MAX_CONSECUTIVE_HOURS_COLUMN, MAX_HOURS_PER_DAY_COLUMN},
+    tsv::Tsv,
+    types::Applicant,
+};
+
+/// Tutors with this tag are never left to run a session on their own
+pub const NEW_TUTOR_TAG: &str = "new";
+/// Tutors with this tag don't mind big weeks followed by empty ones
+pub const BURSTY_TUTOR_TAG: &str = "bursty";
+
+// roster.tsv has a row per tutor (by zID) with ';'-separated tags, e.g. "experienced;lead",
+// and optionally daily limits that take precedence over the tutor's form answers
+pub fn apply_roster(roster: &Tsv, applicants: &mut [Applicant]) {
+    for row in roster {
+        let zid = row.get("zID");
+        let Some(applicant) = applicants.iter_mut().find(|applicant| applicant.zid == zid) else {
+            println!("warning: {zid} is on the roster but didn't fill in the form");
+            continue;
+        };
+
+        applicant.tags = row
+            .get("Tags")
+            .split(';')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect();
+
+        if let Some(max_hours_per_day) = optional_hours(&row, MAX_HOURS_PER_DAY_COLUMN) {
+            applicant.max_hours_per_day = Some(max_hours_per_day);
+        }
+        if let Some(max_consecutive_hours) = optional_hours(&row, MAX_CONSECUTIVE_HOURS_COLUMN) {
+            applicant.max_consecutive_hours = Some(max_consecutive_hours);
+        }
+    }
+}
@@ -1,41 +1,185 @@
 use core::panic;
+use std::collections::HashMap;
 
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     tsv::Tsv,
-    types::{Course, Day, Session, Venue, WeekNum},
+    types::{Course, Day, Session, TagRequirement, Venue, VenueSpec, WeekNum},
 };
 
+/// Named sets usable in sequence specifications, e.g. "teaching" ==> [1, 2, 3, 4, 5, 7, 8, 9, 10]
+pub type SequenceNames = HashMap<String, Vec<i32>>;
+
+// "5", "1-10" or "1-10/2", or a name
+fn expand_sequence_item(item: &str, names: &SequenceNames) -> Result<Vec<i32>, String> {
+    if let Some(named) = names.get(item) {
+        return Ok(named.clone());
+    }
+
+    let parse_num = |num: &str| {
+        num.trim()
+            .parse::<i32>()
+            .map_err(|_| format!("bad number {num:?} in {item:?}"))
+    };
+
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, parse_num(step)?),
+        None => (item, 1),
+    };
+    if step <= 0 {
+        return Err(format!("step must be positive in {item:?}"));
+    }
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_num(start)?, parse_num(end)?),
+        None => {
+            let num = parse_num(range)?;
+            (num, num) // single num
+        }
+    };
+    if start > end {
+        return Err(format!("range {item:?} is backwards"));
+    }
+
+    Ok((start..=end).step_by(step as usize).collect())
+}
+
 // "1-3,5" ==> [1, 2, 3, 5]
-pub fn expand_sequence_specification(spec: &str) -> Vec<i32> {
-    spec.split(',')
-        .flat_map(|range| match range.split_once('-') {
-            Some((start, end)) => {
-                let start_num = start.parse().unwrap();
-                let end_num = end.parse().unwrap();
-                start_num..=end_num
-            }
-            None => {
-                let num = range.parse().unwrap();
-                num..=num // single num
-            }
-        })
-        .collect()
+// "1-10/2" ==> [1, 3, 5, 7, 9]
+// "1-10,!6" ==> [1, 2, 3, 4, 5, 7, 8, 9, 10] (exclusions apply wherever they appear)
+// "teaching,!flex" ==> names come from the `week_sets` in calendar.toml
+pub fn expand_sequence_specification(
+    spec: &str,
+    names: &SequenceNames,
+) -> Result<Vec<i32>, String> {
+    let mut included = vec![];
+    let mut excluded = vec![];
+
+    for item in spec.split(',').map(str::trim) {
+        if item.is_empty() {
+            return Err(format!("empty item in {spec:?}"));
+        }
+
+        match item.strip_prefix('!') {
+            Some(exclusion) => excluded.extend(expand_sequence_item(exclusion, names)?),
+            None => included.extend(expand_sequence_item(item, names)?),
+        }
+    }
+
+    Ok(included
+        .into_iter()
+        .unique()
+        .filter(|num| !excluded.contains(num))
+        .collect())
+}
+
+// "14" or "14:30" ==> minutes after midnight ("24:00" is allowed, as the end of a window)
+pub fn parse_24_hour_time(s: &str) -> Option<u16> {
+    let (hours, minutes) = s.split_once(':').unwrap_or((s, "0"));
+    let hours: u16 = hours.parse().ok()?;
+    let minutes: u16 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60 || hours == 24 && minutes == 0).then(|| hours * 60 + minutes)
 }
 
-fn twelve_hour_to_twentfour_hour(s: &str) -> Option<u8> {
-    if let Some(am) = s.strip_suffix("am") {
-        am.parse().ok()
+// "9am" or "1:30pm" ==> minutes after midnight
+fn twelve_hour_to_minutes(s: &str) -> Option<u16> {
+    let (time, pm) = if let Some(am) = s.strip_suffix("am") {
+        (am, false)
     } else {
-        s.strip_suffix("pm")?
-            .parse()
-            .ok()
-            .map(|hr: u8| if hr == 12 { hr } else { hr + 12 })
+        (s.strip_suffix("pm")?, true)
+    };
+
+    let minutes = parse_24_hour_time(time)?;
+    if !(60..13 * 60).contains(&minutes) {
+        return None;
+    }
+
+    Some(match (pm, minutes >= 12 * 60) {
+        (false, true) => minutes - 12 * 60, // 12:xxam
+        (true, false) => minutes + 12 * 60,
+        _ => minutes,
+    })
+}
+
+// "2hrs", "1.5hrs" or "90mins" ==> minutes
+pub fn parse_length(s: &str) -> Option<u16> {
+    if let Some(minutes) = s.strip_suffix("mins") {
+        return minutes.parse().ok();
     }
+
+    let hours: f32 = s.strip_suffix("hrs")?.parse().ok()?;
+    let minutes = hours * 60.0;
+    (minutes.fract() == 0.0).then_some(minutes as u16)
+}
+
+/// One entry of sessions.toml / sessions.json. Every line of sessions.txt is one of these too,
+/// written as `day start length venue weeks location`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDefinition {
+    /// Only load this session when scheduling this course (all courses if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub course: Option<Course>,
+    /// Used (with the week) as the session key, instead of deriving one from the other fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub day: String,
+    /// e.g. "9am" or "1:30pm"
+    pub start: String,
+    /// e.g. "2hrs", "1.5hrs" or "90mins"
+    pub length: String,
+    /// "f2f", "online" or "hybrid", optionally with a platform: "online:zoom"
+    pub venue: String,
+    /// e.g. "1-5,7-10"
+    pub weeks: String,
+    pub location: String,
+    /// Most tutors this session can take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<u16>,
+    /// Fewest tutors worth running this session with, if it runs at all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_if_open: Option<u16>,
+    /// Tutor tags (from roster.tsv) the session needs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requirements: Vec<TagRequirement>,
+    /// Flags (from rooms.tsv) the room must have, e.g. "accessible"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub room_flags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// Changes to particular weeks of this session, applied in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exceptions: Vec<WeekException>,
+}
+
+/// e.g. a different room in week 5, or no session at all in week 6. The session keeps its key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeekException {
+    pub weeks: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// On top of the session's own requirements, e.g. during assignment weeks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requirements: Vec<TagRequirement>,
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionDefinitionFile {
+    session: Vec<SessionDefinition>,
 }
 
-fn sessions_from_specification_line(line: &str, id: &mut usize) -> Vec<Session> {
+fn definition_from_specification_line(line: &str) -> Option<SessionDefinition> {
     let without_comment = line
         .split_once('#')
         .map(|(before, _)| before)
@@ -43,58 +187,219 @@ fn sessions_from_specification_line(line: &str, id: &mut usize) -> Vec<Session>
         .trim();
 
     if without_comment.is_empty() {
-        return vec![];
+        return None;
     }
 
-    let (day, time, length, venue, weeks, location) = without_comment
+    let (day, start, length, venue, weeks, location) = without_comment
         .split_whitespace()
         .collect_tuple()
         .unwrap_or_else(|| panic!("bad session line: {line:?}"));
 
-    let day = day.parse().unwrap_or_else(|err| panic!("{err}: {line:?}"));
-    let time = twelve_hour_to_twentfour_hour(time)
-        .unwrap_or_else(|| panic!("bad time {time:?} on line {line:?}"));
-    let length = length
-        .strip_suffix("hrs")
-        .and_then(|hrs| hrs.parse().ok())
-        .unwrap_or_else(|| panic!("bad time length {length:?} (on line {line:?})"));
-    let venue = venue
+    Some(SessionDefinition {
+        course: None,
+        label: None,
+        day: day.into(),
+        start: start.into(),
+        length: length.into(),
+        venue: venue.into(),
+        weeks: weeks.into(),
+        location: location.replace('_', " "),
+        capacity: None,
+        min_if_open: None,
+        requirements: vec![],
+        room_flags: vec![],
+        tags: vec![],
+        notes: String::new(),
+        exceptions: vec![],
+    })
+}
+
+// e.g. "COMP1511-w3-Mon-1330-f2f-K17_G07", or "<label>-w3" for labelled sessions
+fn session_key(
+    definition: &SessionDefinition,
+    week: i32,
+    day: Day,
+    start: u16,
+    venue: Venue,
+) -> String {
+    if let Some(label) = &definition.label {
+        return format!("{label}-w{week}");
+    }
+
+    format!(
+        "{}w{week}-{}-{:02}{:02}-{}-{}",
+        definition
+            .course
+            .map_or(String::new(), |course| format!("{course}-")),
+        day.short_name(),
+        start / 60,
+        start % 60,
+        venue.short_name(),
+        definition.location.replace(' ', "_"),
+    )
+}
+
+fn sessions_from_definition(definition: &SessionDefinition, names: &SequenceNames) -> Vec<Session> {
+    let expand_weeks = |weeks: &str| {
+        expand_sequence_specification(weeks, names)
+            .unwrap_or_else(|err| panic!("{err} in {definition:?}"))
+    };
+    let parse_time = |time: &str| {
+        twelve_hour_to_minutes(time)
+            .unwrap_or_else(|| panic!("bad time {time:?} in {definition:?}"))
+    };
+    let parse_session_length = |length: &str| {
+        parse_length(length)
+            .unwrap_or_else(|| panic!("bad time length {length:?} in {definition:?}"))
+    };
+    let parse_venue = |venue: &str| -> VenueSpec {
+        venue
+            .parse()
+            .unwrap_or_else(|err| panic!("{err} in {definition:?}"))
+    };
+
+    let day = definition
+        .day
         .parse()
-        .unwrap_or_else(|()| panic!("bad vanue on {line:?}"));
+        .unwrap_or_else(|err| panic!("{err}: {definition:?}"));
+    let time = parse_time(&definition.start);
+    let length = parse_session_length(&definition.length);
+    let VenueSpec { venue, platform } = parse_venue(&definition.venue);
 
-    expand_sequence_specification(weeks)
+    let exceptions = definition
+        .exceptions
+        .iter()
+        .map(|exception| (expand_weeks(&exception.weeks), exception))
+        .collect::<Vec<_>>();
+
+    expand_weeks(&definition.weeks)
         .into_iter()
-        .map(|week| Session {
-            id: {
-                *id += 1;
-                *id - 1
-            },
-            day,
-            week: WeekNum(week as _),
-            venue,
-            time_24hr: time,
-            length_hours: length,
-            location: location.replace('_', " "),
-            min_allocation: None,
+        .filter_map(|week| {
+            let mut session = Session {
+                key: session_key(definition, week, day, time, venue),
+                copy: 1,
+                day,
+                week: WeekNum(week as _),
+                date: None,
+                venue,
+                platform: platform.clone(),
+                start_minutes: time,
+                length_minutes: length,
+                location: definition.location.clone(),
+                room: None,
+                room_flags: definition.room_flags.clone(),
+                min_allocation: None,
+                max_allocation: None,
+                min_if_open: definition.min_if_open,
+                capacity: definition.capacity,
+                requirements: definition.requirements.clone(),
+                tags: definition.tags.clone(),
+                notes: definition.notes.clone(),
+            };
+
+            for (weeks, exception) in &exceptions {
+                if !weeks.contains(&week) {
+                    continue;
+                }
+                if exception.cancelled {
+                    return None;
+                }
+                if let Some(start) = &exception.start {
+                    session.start_minutes = parse_time(start);
+                }
+                if let Some(length) = &exception.length {
+                    session.length_minutes = parse_session_length(length);
+                }
+                if let Some(venue) = &exception.venue {
+                    let VenueSpec { venue, platform } = parse_venue(venue);
+                    session.venue = venue;
+                    session.platform = platform;
+                }
+                if let Some(location) = &exception.location {
+                    session.location = location.clone();
+                }
+                session
+                    .requirements
+                    .extend(exception.requirements.iter().cloned());
+            }
+
+            Some(session)
         })
         .collect::<Vec<_>>()
 }
 
-pub fn read_sessions_from_string(input: &str) -> Vec<Session> {
-    let mut id = 0;
+fn sessions_from_definitions(
+    definitions: &[SessionDefinition],
+    course: Option<Course>,
+    names: &SequenceNames,
+) -> Vec<Session> {
+    let mut sessions = definitions
+        .iter()
+        .filter(|definition| match (definition.course, course) {
+            (Some(definition_course), Some(course)) => definition_course == course,
+            _ => true,
+        })
+        .flat_map(|definition| sessions_from_definition(definition, names))
+        .collect::<Vec<_>>();
+
+    // identical parallel sessions are numbered in file order: the key, then "key#2", "key#3"...
+    let mut times_seen = HashMap::new();
+    for session in &mut sessions {
+        let count = times_seen.entry(session.key.clone()).or_insert(0);
+        *count += 1;
+        session.copy = *count;
+        if *count > 1 {
+            session.key = format!("{}#{count}", session.key);
+        }
+    }
+
+    sessions
+}
 
+pub fn read_definitions_from_string(input: &str) -> Vec<SessionDefinition> {
     input
         .lines()
-        .flat_map(|line| sessions_from_specification_line(line, &mut id))
+        .filter_map(definition_from_specification_line)
         .collect()
 }
 
+pub fn read_sessions_from_string(input: &str, names: &SequenceNames) -> Vec<Session> {
+    sessions_from_definitions(&read_definitions_from_string(input), None, names)
+}
+
+pub fn read_sessions_from_toml(
+    input: &str,
+    course: Option<Course>,
+    names: &SequenceNames,
+) -> Vec<Session> {
+    let file: SessionDefinitionFile =
+        toml::from_str(input).unwrap_or_else(|err| panic!("bad session definitions: {err}"));
+    sessions_from_definitions(&file.session, course, names)
+}
+
+pub fn read_sessions_from_json(
+    input: &str,
+    course: Option<Course>,
+    names: &SequenceNames,
+) -> Vec<Session> {
+    let file: SessionDefinitionFile =
+        serde_json::from_str(input).unwrap_or_else(|err| panic!("bad session definitions: {err}"));
+    sessions_from_definitions(&file.session, course, names)
+}
+
+pub fn definitions_to_toml(definitions: Vec<SessionDefinition>) -> String {
+    toml::to_string(&SessionDefinitionFile {
+        session: definitions,
+    })
+    .unwrap()
+}
+
 pub fn extract_desired_hours(tsv: Tsv, course: Course) -> Vec<(WeekNum, u16)> {
     tsv.into_iter()
         .map(|row| {
             let week = row.get("Week").parse().unwrap();
             let hours = row
-                .get(&format!("Desired {} hours", course.to_string()))
+                .get(&format!("Desired {} hours", course))
                 .parse()
                 .unwrap();
             (WeekNum(week), hours)
@@ -102,37 +407,293 @@ pub fn extract_desired_hours(tsv: Tsv, course: Course) -> Vec<(WeekNum, u16)> {
         .collect()
 }
 
-pub fn apply_priorities(course: Course, priorities: &Tsv, sessions: &mut [Session]) {
+fn is_wildcard(field: &str) -> bool {
+    field.is_empty() || field == "*"
+}
+
+// Rows refer to sessions by key (in an optional "Session" column), or by any combination of
+// weeks, day, venue and start time, where a blank or "*" field matches anything.
+//
+// Where rows overlap, the more specific row (the one matching fewer sessions, so a key or a
+// single week beats a range) sets each of the minimum, maximum and minimum-if-open, and later
+// rows win ties.
+//
+// Rows only for sessions the calendar dropped (in the flexibility week or on a holiday) are
+// skipped with a warning.
+pub fn apply_priorities(
+    course: Course,
+    priorities: &Tsv,
+    sessions: &mut [Session],
+    dropped: &[Session],
+    names: &SequenceNames,
+) {
+    // how many sessions the row that set each value matched
+    let mut minimum_matches: Vec<Option<usize>> = vec![None; sessions.len()];
+    let mut maximum_matches: Vec<Option<usize>> = vec![None; sessions.len()];
+    let mut min_if_open_matches: Vec<Option<usize>> = vec![None; sessions.len()];
+
     for row in priorities {
         if row.get("Course").parse::<Course>().unwrap() != course {
             continue;
         }
 
-        let week = WeekNum(row.get("Week").parse().unwrap());
-        let day: Day = row.get("Day").parse().unwrap();
-        let venue: Venue = row.get("Venue").parse().unwrap();
-        let start: u8 = row.get("Start").parse().unwrap();
-        let minimum: u16 = row.get("Minimum").parse().unwrap();
-
-        let mut matching_sesions = sessions.iter_mut().filter(|session| {
-            session.week == week
-                && session.venue == venue
-                && session.time_24hr == start
-                && session.day == day
+        let field = |column: &str| row.try_get(column).filter(|field| !is_wildcard(field));
+        let bad_row = || format!("in priority {:?}", row.all_fields());
+
+        let session_key = field("Session");
+        let weeks = field("Week").map(|weeks| {
+            expand_sequence_specification(weeks, names)
+                .unwrap_or_else(|err| panic!("{err} {}", bad_row()))
+        });
+        let day: Option<Day> = field("Day").map(|day| {
+            day.parse()
+                .unwrap_or_else(|err| panic!("{err} {}", bad_row()))
+        });
+        let venue: Option<VenueSpec> = field("Venue").map(|venue| {
+            venue
+                .parse()
+                .unwrap_or_else(|err| panic!("{err} {}", bad_row()))
+        });
+        let start = field("Start").map(|start| {
+            parse_24_hour_time(start)
+                .unwrap_or_else(|| panic!("bad start time {start:?} {}", bad_row()))
         });
-        let session = matching_sesions
-            .next()
-            .unwrap_or_else(|| panic!("No matching session for priority {:?}", row.all_fields()));
-        if matching_sesions.next().is_some() {
-            panic!(
-                "Multiple matching sessions for priority {:?}",
-                row.all_fields()
-            )
+        let minimum: Option<u16> = field("Minimum").map(|minimum| minimum.parse().unwrap());
+        let maximum: Option<u16> = field("Maximum").map(|maximum| maximum.parse().unwrap());
+        let min_if_open: Option<u16> =
+            field("Minimum if open").map(|min_if_open| min_if_open.parse().unwrap());
+
+        let row_matches = |session: &Session| match session_key {
+            Some(key) => session.key == key,
+            None => {
+                weeks
+                    .as_ref()
+                    .is_none_or(|weeks| weeks.contains(&(session.week.0 as i32)))
+                    && day.is_none_or(|day| session.day == day)
+                    && venue.as_ref().is_none_or(|venue| venue.matches(session))
+                    && start.is_none_or(|start| session.start_minutes == start)
+            }
+        };
+        let matching = sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, session)| row_matches(session))
+            .map(|(session_index, _)| session_index)
+            .collect::<Vec<_>>();
+
+        if matching.is_empty() {
+            if dropped.iter().any(row_matches) {
+                println!(
+                    "warning: ignoring priority {:?}, the calendar dropped its sessions",
+                    row.all_fields()
+                );
+                continue;
+            }
+            panic!("No matching session for priority {:?}", row.all_fields());
         }
-        if session.min_allocation.is_some() {
-            panic!("Multiple priorities for {:?}", session)
+        let num_matching = matching.len();
+
+        for session_index in matching {
+            let session = &mut sessions[session_index];
+
+            if let Some(minimum) = minimum {
+                let previous = &mut minimum_matches[session_index];
+                if previous.is_none_or(|previous| num_matching <= previous) {
+                    session.min_allocation = Some(minimum);
+                    *previous = Some(num_matching);
+                }
+            }
+
+            if let Some(maximum) = maximum {
+                let previous = &mut maximum_matches[session_index];
+                if previous.is_none_or(|previous| num_matching <= previous) {
+                    session.max_allocation = Some(maximum);
+                    *previous = Some(num_matching);
+                }
+            }
+
+            if let Some(min_if_open) = min_if_open {
+                let previous = &mut min_if_open_matches[session_index];
+                if previous.is_none_or(|previous| num_matching <= previous) {
+                    session.min_if_open = Some(min_if_open);
+                    *previous = Some(num_matching);
+                }
+            }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(spec: &str) -> Result<Vec<i32>, String> {
+        let names = SequenceNames::from([
+            ("teaching".to_string(), vec![1, 2, 3, 4, 5, 7, 8, 9, 10]),
+            ("flex".to_string(), vec![6]),
+        ]);
+        expand_sequence_specification(spec, &names)
+    }
+
+    #[test]
+    fn numbers_and_ranges() {
+        assert_eq!(expand("5"), Ok(vec![5]));
+        assert_eq!(expand("1-3,5"), Ok(vec![1, 2, 3, 5]));
+        assert_eq!(expand(" 1 - 3 , 2 "), Ok(vec![1, 2, 3]));
+        assert_eq!(expand("4-4"), Ok(vec![4]));
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(expand("1-10/2"), Ok(vec![1, 3, 5, 7, 9]));
+        assert_eq!(expand("2-10/4"), Ok(vec![2, 6, 10]));
+        assert_eq!(expand("3/2"), Ok(vec![3]));
+        assert!(expand("1-10/0").is_err());
+        assert!(expand("1-10/-1").is_err());
+        assert!(expand("1-10/x").is_err());
+    }
+
+    #[test]
+    fn exclusions_apply_wherever_they_appear() {
+        assert_eq!(expand("1-5,!3"), Ok(vec![1, 2, 4, 5]));
+        assert_eq!(expand("!3,1-5"), Ok(vec![1, 2, 4, 5]));
+        assert_eq!(expand("1-10/3,!4-7"), Ok(vec![1, 10]));
+        assert_eq!(expand("1,!1"), Ok(vec![]));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(expand("flex"), Ok(vec![6]));
+        assert_eq!(
+            expand("teaching,!flex,!1"),
+            Ok(vec![2, 3, 4, 5, 7, 8, 9, 10])
+        );
+        assert_eq!(expand("1-10,!teaching"), Ok(vec![6]));
+        assert!(expand("holidays").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            expand("5-1"),
+            Err(r#"range "5-1" is backwards"#.to_string())
+        );
+        assert_eq!(expand("1,,2"), Err(r#"empty item in "1,,2""#.to_string()));
+        assert!(expand("").is_err());
+        assert!(expand("1,").is_err());
+        assert!(expand("!").is_err());
+        assert!(expand("1-").is_err());
+        assert!(expand("a-b").is_err());
+    }
+
+    #[test]
+    fn identical_lines_are_numbered_copies() {
+        let sessions = read_sessions_from_string(
+            "mon 9am 2hrs f2f 1-2 K17_G07\nmon 9am 2hrs f2f 1-2 K17_G07\n",
+            &SequenceNames::new(),
+        );
+        let keys_and_copies = sessions
+            .iter()
+            .map(|session| (session.key.as_str(), session.copy))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys_and_copies,
+            [
+                ("w1-Mon-0900-f2f-K17_G07", 1),
+                ("w2-Mon-0900-f2f-K17_G07", 1),
+                ("w1-Mon-0900-f2f-K17_G07#2", 2),
+                ("w2-Mon-0900-f2f-K17_G07#2", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn priorities_for_dropped_sessions_are_skipped() {
+        let names = SequenceNames::new();
+        let (mut sessions, dropped): (Vec<_>, Vec<_>) =
+            read_sessions_from_string("mon 9am 2hrs f2f 5-7 K17_G07\n", &names)
+                .into_iter()
+                .partition(|session| session.week != WeekNum(6));
+        let priorities = Tsv::from_string(
+            "Course\tWeek\tMinimum\n\
+             COMP1511\t6\t3\n\
+             COMP1511\t5\t2\n",
+        );
+        apply_priorities(
+            Course::Comp1511,
+            &priorities,
+            &mut sessions,
+            &dropped,
+            &names,
+        );
+
+        let minimums = sessions
+            .iter()
+            .map(|session| session.min_allocation)
+            .collect::<Vec<_>>();
+        assert_eq!(minimums, [Some(2), None]);
+    }
+
+    #[test]
+    fn more_specific_priorities_win_whatever_their_order() {
+        let names = SequenceNames::new();
+        let mut sessions = read_sessions_from_string("mon 9am 2hrs f2f 1-10 K17_G07\n", &names);
+        let priorities = Tsv::from_string(
+            "Course\tWeek\tDay\tVenue\tStart\tMinimum\n\
+             COMP1511\t3\t*\t*\t*\t4\n\
+             COMP1511\t1-10\t*\t*\t*\t2\n\
+             COMP1511\t*\tmon\tf2f\t9\t1\n",
+        );
+        apply_priorities(Course::Comp1511, &priorities, &mut sessions, &[], &names);
+
+        let minimums = sessions
+            .iter()
+            .map(|session| session.min_allocation)
+            .collect::<Vec<_>>();
+        let mut expected = vec![Some(1); 10];
+        expected[2] = Some(4);
+        assert_eq!(minimums, expected);
+    }
+
+    #[test]
+    fn twelve_hour_times() {
+        assert_eq!(twelve_hour_to_minutes("9am"), Some(9 * 60));
+        assert_eq!(twelve_hour_to_minutes("1:30pm"), Some(13 * 60 + 30));
+        assert_eq!(twelve_hour_to_minutes("12am"), Some(0));
+        assert_eq!(twelve_hour_to_minutes("12:15am"), Some(15));
+        assert_eq!(twelve_hour_to_minutes("12pm"), Some(12 * 60));
+        assert_eq!(twelve_hour_to_minutes("12:30pm"), Some(12 * 60 + 30));
+        assert_eq!(twelve_hour_to_minutes("11:59pm"), Some(23 * 60 + 59));
+        assert_eq!(twelve_hour_to_minutes("0am"), None);
+        assert_eq!(twelve_hour_to_minutes("13pm"), None);
+        assert_eq!(twelve_hour_to_minutes("9:60am"), None);
+        assert_eq!(twelve_hour_to_minutes("9"), None);
+        assert_eq!(twelve_hour_to_minutes("14:00"), None);
+    }
+
+    #[test]
+    fn twenty_four_hour_times() {
+        assert_eq!(parse_24_hour_time("14"), Some(14 * 60));
+        assert_eq!(parse_24_hour_time("09:05"), Some(9 * 60 + 5));
+        assert_eq!(parse_24_hour_time("24"), Some(24 * 60));
+        assert_eq!(parse_24_hour_time("24:00"), Some(24 * 60));
+        assert_eq!(parse_24_hour_time("24:30"), None);
+        assert_eq!(parse_24_hour_time("24:59"), None);
+        assert_eq!(parse_24_hour_time("25"), None);
+        assert_eq!(parse_24_hour_time("9999"), None);
+        assert_eq!(parse_24_hour_time("9:75"), None);
+        assert_eq!(parse_24_hour_time("9am"), None);
+    }
 
-        session.min_allocation = Some(minimum);
+    #[test]
+    fn lengths() {
+        assert_eq!(parse_length("2hrs"), Some(120));
+        assert_eq!(parse_length("1.5hrs"), Some(90));
+        assert_eq!(parse_length("0.25hrs"), Some(15));
+        assert_eq!(parse_length("90mins"), Some(90));
+        assert_eq!(parse_length("1.01hrs"), None);
+        assert_eq!(parse_length("2"), None);
+        assert_eq!(parse_length("2 hrs"), None);
+        assert_eq!(parse_length("hrs"), None);
     }
 }
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::{solution_output::SolvedSession, types::Session};
+
+/// Someone covering a week of a whole-term slot its usual tutor can't do
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    pub session_key: String,
+    pub absent_zid: String,
+    /// None when no one could cover
+    pub substitute_zid: Option<String>,
+}
+
+// The same day, time, venue and location in every week, e.g. "Tue-1500-online-Teams"
+// (parallel copies get their "#2" too, so they're separate slots)
+pub fn slot_key(session: &Session) -> String {
+    let copy = match session.copy {
+        1 => String::new(),
+        copy => format!("#{copy}"),
+    };
+    format!(
+        "{}-{:02}{:02}-{}-{}{copy}",
+        session.day.short_name(),
+        session.start_minutes / 60,
+        session.start_minutes % 60,
+        session.venue.short_name(),
+        session.location.replace(' ', "_"),
+    )
+}
+
+// Session indexes grouped by slot, each group in week order, in order of first appearance
+pub fn recurring_slots(sessions: &[Session]) -> Vec<(String, Vec<usize>)> {
+    let mut slots: Vec<(String, Vec<usize>)> = vec![];
+    let mut slot_positions = HashMap::new();
+
+    for (session_index, session) in sessions.iter().enumerate() {
+        let key = slot_key(session);
+        let position = *slot_positions.entry(key.clone()).or_insert_with(|| {
+            slots.push((key, vec![]));
+            slots.len() - 1
+        });
+        slots[position].1.push(session_index);
+    }
+
+    for (_, session_indexes) in &mut slots {
+        session_indexes.sort_by_key(|&index| sessions[index].week);
+    }
+
+    slots
+}
+
+// How many tutors each slot kept from one week it ran to the next, one line per recurring slot
+pub fn report_continuity(solution: &[SolvedSession]) -> Vec<String> {
+    let sessions = solution
+        .iter()
+        .map(|solved| solved.session.clone())
+        .collect::<Vec<_>>();
+
+    recurring_slots(&sessions)
+        .into_iter()
+        .filter(|(_, session_indexes)| session_indexes.len() > 1)
+        .map(|(key, session_indexes)| {
+            let staffed = session_indexes
+                .iter()
+                .map(|&index| &solution[index])
+                .filter(|solved| !solved.applicants.is_empty())
+                .collect::<Vec<_>>();
+
+            let mut kept = 0;
+            let mut places = 0;
+            for pair in staffed.windows(2) {
+                let (before, after) = (pair[0], pair[1]);
+                kept += before
+                    .applicants
+                    .iter()
+                    .filter(|applicant| {
+                        after
+                            .applicants
+                            .iter()
+                            .any(|other| other.zid == applicant.zid)
+                    })
+                    .count();
+                places += before.applicants.len().max(after.applicants.len());
+            }
+
+            format!(
+                "{key}: kept {kept} of {places} tutors from week to week, \
+                 running in {} of {} weeks",
+                staffed.len(),
+                session_indexes.len()
+            )
+        })
+        .collect()
+}
+
+pub fn tabulate_substitutions(substitutions: &[Substitution]) -> String {
+    let mut tsv = String::from("Session\tAbsent\tSubstitute\n");
+    for substitution in substitutions {
+        tsv += &format!(
+            "{}\t{}\t{}\n",
+            substitution.session_key,
+            substitution.absent_zid,
+            substitution.substitute_zid.as_deref().unwrap_or("")
+        );
+    }
+    tsv
+}
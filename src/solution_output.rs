@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    solver::Seed,
+    types::{Applicant, Course, Session, WeekNum},
+};
+
+#[derive(Debug, Clone)]
+pub struct SolvedSession {
+    pub session: Session,
+    pub applicants: Vec<Applicant>,
+}
+
+// minutes after midnight ==> "14:30"
+fn format_24_hour(time: u16) -> String {
+    format!("{:02}:{:02}", time / 60, time % 60)
+}
+
+// minutes ==> hours, e.g. "2" or "1.5"
+fn format_hours(minutes: u32) -> String {
+    if minutes.is_multiple_of(60) {
+        return (minutes / 60).to_string();
+    }
+
+    format!("{:.2}", minutes as f32 / 60.0)
+        .trim_end_matches('0')
+        .to_string()
+}
+
+fn zids(applicants: &[Applicant]) -> String {
+    applicants
+        .iter()
+        .map(|applicant| applicant.zid.as_str())
+        .join(", ")
+}
+
+// One row per session, with its tutors
+pub fn tabulate_solution_info(solution: Vec<SolvedSession>) -> String {
+    let mut tsv = [
+        "Session", "Week", "Date", "Day", "Start", "End", "Venue", "Platform", "Location",
+        "Campus", "Tags", "Notes", "Tutors", "zIDs",
+    ]
+    .join("\t")
+        + "\n";
+
+    for solved in &solution {
+        let session = &solved.session;
+        let row = [
+            session.key.clone(),
+            session.week.0.to_string(),
+            session.date.map_or(String::new(), |date| date.to_string()),
+            session.day.long_name().into(),
+            format_24_hour(session.start_minutes),
+            format_24_hour(session.end_minutes()),
+            session.venue.short_name().into(),
+            session.platform.clone().unwrap_or_default(),
+            session.location.clone(),
+            session
+                .room
+                .as_ref()
+                .map_or(String::new(), |room| room.campus.clone()),
+            session.tags.join(";"),
+            session.notes.clone(),
+            solved
+                .applicants
+                .iter()
+                .map(|applicant| applicant.name.as_str())
+                .join(", "),
+            zids(&solved.applicants),
+        ];
+        tsv += &(row.join("\t") + "\n");
+    }
+
+    tsv
+}
+
+// One row per tutor, with their hours in each week and over the term
+pub fn tabulate_hours_by_tutor(solution: Vec<SolvedSession>) -> String {
+    let weeks = solution
+        .iter()
+        .map(|solved| solved.session.week)
+        .unique()
+        .sorted()
+        .collect::<Vec<_>>();
+
+    // zID ==> (tutor, minutes per week)
+    let mut tutors: BTreeMap<&str, (&Applicant, BTreeMap<WeekNum, u32>)> = BTreeMap::new();
+    for solved in &solution {
+        for applicant in &solved.applicants {
+            *tutors
+                .entry(&applicant.zid)
+                .or_insert_with(|| (applicant, BTreeMap::new()))
+                .1
+                .entry(solved.session.week)
+                .or_default() += solved.session.length_minutes as u32;
+        }
+    }
+
+    let mut header = vec!["zID".to_string(), "Name".into(), "Email".into()];
+    header.extend(weeks.iter().map(|week| format!("Week {}", week.0)));
+    header.push("Total".into());
+    let mut tsv = header.join("\t") + "\n";
+
+    for (zid, (applicant, minutes_by_week)) in tutors {
+        let mut row = vec![
+            zid.to_string(),
+            applicant.name.clone(),
+            applicant.email.clone(),
+        ];
+        row.extend(
+            weeks
+                .iter()
+                .map(|week| format_hours(minutes_by_week.get(week).copied().unwrap_or_default())),
+        );
+        row.push(format_hours(minutes_by_week.values().sum()));
+        tsv += &(row.join("\t") + "\n");
+    }
+
+    tsv
+}
+
+#[derive(Serialize)]
+struct OutputSession {
+    key: String,
+    week: u8,
+    /// "2024-09-09", when there's a term calendar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    day: &'static str,
+    start: String,
+    end: String,
+    venue: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform: Option<String>,
+    location: String,
+    tutors: Vec<String>,
+}
+
+impl OutputSession {
+    fn new(solved: &SolvedSession) -> Self {
+        let session = &solved.session;
+        OutputSession {
+            key: session.key.clone(),
+            week: session.week.0,
+            date: session.date.map(|date| date.to_string()),
+            day: session.day.short_name(),
+            start: format_24_hour(session.start_minutes),
+            end: format_24_hour(session.end_minutes()),
+            venue: session.venue.short_name(),
+            platform: session.platform.clone(),
+            location: session.location.clone(),
+            tutors: solved
+                .applicants
+                .iter()
+                .map(|applicant| applicant.zid.clone())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AtciFile {
+    seed: Seed,
+    session: Vec<OutputSession>,
+}
+
+// help_sessions.<course>.toml: the seed, then a [[session]] table per session with its tutors'
+// zIDs
+pub fn output_to_atci_toml(solution: Vec<SolvedSession>, seed: Seed) -> String {
+    let file = AtciFile {
+        seed,
+        session: solution.iter().map(OutputSession::new).collect(),
+    };
+    toml::to_string(&file).unwrap()
+}
+
+#[derive(Serialize)]
+struct JsonFile {
+    course: String,
+    seed: Seed,
+    sessions: Vec<OutputSession>,
+}
+
+// help_sessions.<course>.json: the course, the seed and a list of sessions shaped like the TOML's
+pub fn convert_to_json_output(solution: Vec<SolvedSession>, seed: Seed, course: Course) -> String {
+    let file = JsonFile {
+        course: course.to_string(),
+        seed,
+        sessions: solution.iter().map(OutputSession::new).collect(),
+    };
+    serde_json::to_string_pretty(&file).unwrap()
+}
@@ -1,37 +1,221 @@
 use core::panic;
-use std::{
-    cmp::{max, min},
-    collections::HashSet,
-};
+use std::{cell::RefCell, collections::HashSet};
 
+use itertools::Itertools;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use smallvec::SmallVec;
 
 use crate::{
+    read_roster::{BURSTY_TUTOR_TAG, NEW_TUTOR_TAG},
+    slots::{recurring_slots, Substitution},
     solution_output::SolvedSession,
-    types::{Applicant, Availability, Course, Session, WeekNum},
+    types::{Applicant, Availability, Course, Pairing, PairingKind, Session, WeekNum},
 };
 
-const MAX_TUTORS_PER_SESSION: usize = 5;
+/// Used when neither the session definition nor priorities.tsv give a maximum
+const DEFAULT_MAX_TUTORS_PER_SESSION: usize = 5;
 type ApplicantId = u16;
 type HourCount = u16;
-type Cost = u64;
+type MinuteCount = u32;
+pub type Cost = u64;
 pub type Seed = u64;
 
 #[derive(Debug, Clone)]
 struct SessionAllocation {
-    assigned: SmallVec<[ApplicantId; MAX_TUTORS_PER_SESSION]>,
+    assigned: SmallVec<[ApplicantId; DEFAULT_MAX_TUTORS_PER_SESSION]>,
+}
+
+fn max_tutors(session: &Session) -> usize {
+    [
+        session.capacity,
+        session.max_allocation,
+        session.room.as_ref().map(|room| room.capacity),
+    ]
+    .into_iter()
+    .flatten()
+    .min()
+    .map_or(DEFAULT_MAX_TUTORS_PER_SESSION, |max_tutors| {
+        max_tutors as usize
+    })
+}
+
+fn hours_to_minutes(hours: HourCount) -> MinuteCount {
+    hours as MinuteCount * 60
+}
+
+fn minutes_to_hours(minutes: MinuteCount) -> f32 {
+    minutes as f32 / 60.0
+}
+
+/// Settings that apply to every tutor, unless their own form or roster answers say otherwise
+#[derive(Debug, Clone, Default)]
+pub struct SolverOptions {
+    /// Far fewer annealing steps, for trying things out
+    pub quick: bool,
+    pub max_hours_per_day: Option<HourCount>,
+    pub max_consecutive_hours: Option<HourCount>,
+    pub travel_gap: Option<TravelGap>,
+    /// Cost of each day a tutor has to come in to campus, so in-person sessions get bunched
+    pub campus_day_cost: Cost,
+    /// Cost per hour a tutor waits on campus between in-person sessions on a day
+    pub campus_idle_hour_cost: Cost,
+    /// Most days a week a tutor comes in to campus
+    pub max_campus_days: Option<u8>,
+    /// Cost of each tutor who joins or leaves a recurring slot between weeks
+    pub slot_change_cost: Cost,
+    /// Tutors are given recurring slots for the whole term rather than single sessions, with
+    /// substitutes for the weeks they can't make
+    pub whole_term: bool,
+    /// Cost per square hour of variance in a tutor's weekly hours, over the weeks they're
+    /// available
+    pub hours_variance_cost: Cost,
+}
+
+/// Time a tutor needs between sessions to get from campus to home (or another campus)
+#[derive(Debug, Clone)]
+pub struct TravelGap {
+    pub minutes: u16,
+    /// None makes a short gap impossible
+    pub penalty: Option<Cost>,
+}
+
+// going from in-person to online or back, or between campuses
+fn needs_travel(before: &Session, after: &Session) -> bool {
+    // only in-person sessions have rooms
+    match (&before.room, &after.room) {
+        _ if before.venue.is_in_person() != after.venue.is_in_person() => true,
+        (Some(before_room), Some(after_room)) => before_room.campus != after_room.campus,
+        _ => false,
+    }
 }
 
 struct Week {
-    desired_total_hours: HourCount,
+    desired_total_minutes: MinuteCount,
     session_indexes: Vec<usize>,
+    /// indexed by applicant: whether they can do any of this week's sessions
+    available: Vec<bool>,
+}
+
+struct SkillRequirement {
+    at_least: usize,
+    hard: bool,
+    /// indexed by applicant
+    qualified: Vec<bool>,
+}
+
+struct DailyLimits {
+    max_minutes_per_day: Option<MinuteCount>,
+    max_consecutive_minutes: Option<MinuteCount>,
+}
+
+// Buffers eval_allocation reuses rather than allocating on every step, all indexed by applicant
+struct EvalScratch {
+    overall_total: Vec<MinuteCount>,
+    weekly_total: Vec<MinuteCount>,
+    /// weekly hours (and their squares) over the weeks each applicant is available
+    hours_sum: Vec<f32>,
+    hours_squared_sum: Vec<f32>,
+    sessions_this_week: Vec<Vec<usize>>,
+}
+
+// A week a whole-term slot's tutor can't make
+struct Gap {
+    session: usize,
+    absent: ApplicantId,
+    substitute: Option<ApplicantId>,
+}
+
+/// The cost of an allocation, term by term
+#[derive(Debug, Default)]
+pub struct CostBreakdown {
+    pub availability: Cost,
+    /// Back-to-back with a class on another campus
+    pub class_clashes: Cost,
+    pub pairings: Cost,
+    pub requirements: Cost,
+    /// Below a session's minimum, or its minimum if open
+    pub understaffed: Cost,
+    /// Sessions much fuller than others in the same week
+    pub fill_spread: Cost,
+    /// Short of tutors' minimum weekly hours, or of 6 hours over the term
+    pub tutor_hours: Cost,
+    /// Short of or over each week's desired total
+    pub desired_hours: Cost,
+    /// Soft travel gaps, campus days and waiting on campus
+    pub days: Cost,
+    pub slot_changes: Cost,
+    pub hours_variance: Cost,
+    /// Sessions staffed only by new tutors. This is a hard constraint, so it's only ever counted
+    /// for a finished solution (and should be 0).
+    pub new_tutors_alone: usize,
+}
+
+impl CostBreakdown {
+    pub fn total(&self) -> Cost {
+        self.availability
+            + self.class_clashes
+            + self.pairings
+            + self.requirements
+            + self.understaffed
+            + self.fill_spread
+            + self.tutor_hours
+            + self.desired_hours
+            + self.days
+            + self.slot_changes
+            + self.hours_variance
+    }
+
+    // one line per term, e.g. "availability: 120"
+    pub fn lines(&self) -> Vec<String> {
+        [
+            ("availability", self.availability),
+            ("class clashes", self.class_clashes),
+            ("pairings", self.pairings),
+            ("requirements", self.requirements),
+            ("understaffed", self.understaffed),
+            ("fill spread", self.fill_spread),
+            ("tutor hours", self.tutor_hours),
+            ("desired hours", self.desired_hours),
+            ("days", self.days),
+            ("slot changes", self.slot_changes),
+            ("hours variance", self.hours_variance),
+        ]
+        .into_iter()
+        .map(|(term, cost)| format!("{term}: {cost}"))
+        .chain([format!(
+            "new tutors alone: {} sessions (hard)",
+            self.new_tutors_alone
+        )])
+        .collect()
+    }
+}
+
+struct PairingCost {
+    applicants: (ApplicantId, ApplicantId),
+    kind: PairingKind,
+    /// None for a hard constraint
+    weight: Option<Cost>,
 }
 
 struct Solver<'a> {
     sessions: &'a [Session],
     applicants: &'a [Applicant],
     weeks: Vec<Week>,
+    /// indexed by session
+    requirements: Vec<Vec<SkillRequirement>>,
+    /// indexed by applicant
+    new_tutors: Vec<bool>,
+    /// indexed by applicant: tagged bursty, so exempt from evening out their weeks
+    bursty: Vec<bool>,
+    pairings: Vec<PairingCost>,
+    /// indexed by applicant
+    daily_limits: Vec<DailyLimits>,
+    /// indexed by applicant: whether eval_days has anything to check
+    checks_days: Vec<bool>,
+    /// Session indexes of each recurring slot, in week order
+    slots: Vec<Vec<usize>>,
+    options: &'a SolverOptions,
+    scratch: RefCell<EvalScratch>,
 }
 
 #[derive(Debug)]
@@ -39,34 +223,140 @@ struct Solver<'a> {
 enum Mutation {
     AddToSession { session: usize, applicant: u16 },
     RemoveFromSession { session: usize, applicant: u16 },
+    AddToSlot { slot: usize, applicant: u16 },
+    RemoveFromSlot { slot: usize, applicant: u16 },
 }
 
 impl<'a> Solver<'a> {
+    // None if the applicant's week breaks their daily or consecutive hours limits, a hard
+    // travel gap or the campus days cap; otherwise the cost of soft travel gaps and campus days
+    fn eval_days(&self, applicant_index: usize, session_indexes: &mut [usize]) -> Option<Cost> {
+        let limits = &self.daily_limits[applicant_index];
+        let mut cost = 0;
+        let mut num_campus_days = 0;
+
+        session_indexes.sort_by_key(|&index| {
+            let session = &self.sessions[index];
+            (session.day, session.start_minutes)
+        });
+
+        for day_sessions in session_indexes
+            .chunk_by(|&first, &second| self.sessions[first].day == self.sessions[second].day)
+        {
+            let day_sessions = day_sessions.iter().map(|&index| &self.sessions[index]);
+
+            if let Some(max_minutes) = limits.max_minutes_per_day {
+                let day_minutes = day_sessions
+                    .clone()
+                    .map(|session| session.length_minutes as MinuteCount)
+                    .sum::<MinuteCount>();
+                if day_minutes > max_minutes {
+                    return None;
+                }
+            }
+
+            if let Some(max_minutes) = limits.max_consecutive_minutes {
+                // (start, end) of the current run of back-to-back sessions
+                let mut run: Option<(u16, u16)> = None;
+                for session in day_sessions.clone() {
+                    run = match run {
+                        Some((start, end)) if session.start_minutes <= end => {
+                            Some((start, end.max(session.end_minutes())))
+                        }
+                        _ => Some((session.start_minutes, session.end_minutes())),
+                    };
+                    let (start, end) = run.unwrap();
+                    if (end - start) as MinuteCount > max_minutes {
+                        return None;
+                    }
+                }
+            }
+
+            if let Some(travel_gap) = &self.options.travel_gap {
+                for (before, after) in day_sessions.clone().tuple_windows() {
+                    let gap = after.start_minutes.saturating_sub(before.end_minutes());
+                    if gap < travel_gap.minutes && needs_travel(before, after) {
+                        cost += travel_gap.penalty?;
+                    }
+                }
+            }
+
+            let mut in_person = day_sessions.filter(|session| session.venue.is_in_person());
+            if let Some(first) = in_person.next() {
+                num_campus_days += 1;
+                cost += self.options.campus_day_cost;
+
+                // so back-to-back sessions beat ones with hours to kill in between
+                if self.options.campus_idle_hour_cost > 0 {
+                    let mut end = first.end_minutes();
+                    for session in in_person {
+                        let idle_minutes = session.start_minutes.saturating_sub(end);
+                        cost += self.options.campus_idle_hour_cost * idle_minutes as Cost / 60;
+                        end = end.max(session.end_minutes());
+                    }
+                }
+            }
+        }
+
+        if self
+            .options
+            .max_campus_days
+            .is_some_and(|max_campus_days| num_campus_days > max_campus_days)
+        {
+            return None;
+        }
+
+        Some(cost)
+    }
+
     fn eval_allocation(&self, allocations: &[SessionAllocation]) -> Option<Cost> {
-        let mut total_cost: Cost = 0;
+        self.eval_allocation_breakdown(allocations)
+            .map(|breakdown| breakdown.total())
+    }
+
+    // None if the allocation breaks a hard constraint
+    fn eval_allocation_breakdown(
+        &self,
+        allocations: &[SessionAllocation],
+    ) -> Option<CostBreakdown> {
+        let mut breakdown = CostBreakdown::default();
 
-        let mut applicant_overall_total: Vec<HourCount> = [0].repeat(self.applicants.len());
+        let scratch = &mut *self.scratch.borrow_mut();
+        let EvalScratch {
+            overall_total: applicant_overall_total,
+            weekly_total: applicant_weekly_total,
+            hours_sum: applicant_hours_sum,
+            hours_squared_sum: applicant_hours_squared_sum,
+            sessions_this_week: applicant_sessions_this_week,
+        } = scratch;
+        applicant_overall_total.fill(0);
+        applicant_hours_sum.fill(0.0);
+        applicant_hours_squared_sum.fill(0.0);
 
         for week in &self.weeks {
-            let mut effective_hours_this_week = 0;
-            let mut applicant_weekly_total: Vec<HourCount> = [0].repeat(self.applicants.len());
+            let mut effective_minutes_this_week = 0;
+            applicant_weekly_total.fill(0);
+            for applicant_sessions in applicant_sessions_this_week.iter_mut() {
+                applicant_sessions.clear();
+            }
 
-            let mut min_size_this_week = MAX_TUTORS_PER_SESSION;
-            let mut max_size_this_week = 0;
+            // how full sessions are, relative to their own maximum
+            let mut min_fill_this_week: f32 = 1.0;
+            let mut max_fill_this_week: f32 = 0.0;
 
             for session_index in week.session_indexes.iter().copied() {
                 let allocation = &allocations[session_index];
                 let session = &self.sessions[session_index];
 
-                let session_length = session.length_hours as HourCount;
-                let effective_hours = session_length * (allocation.assigned.len() as HourCount);
+                let session_length = session.length_minutes as MinuteCount;
+                let effective_minutes = session_length * (allocation.assigned.len() as MinuteCount);
 
-                effective_hours_this_week += effective_hours;
+                effective_minutes_this_week += effective_minutes;
 
                 for applicant_index in allocation.assigned.iter().copied() {
-                    let availability =
-                        self.applicants[applicant_index as usize].availabilities[session_index];
-                    total_cost += match availability {
+                    let applicant = &self.applicants[applicant_index as usize];
+                    breakdown.class_clashes += applicant.extra_costs[session_index];
+                    breakdown.availability += match applicant.availabilities[session_index] {
                         Availability::Impossible => return None,
                         Availability::Dislike => 100,
                         Availability::Possible => 5,
@@ -75,68 +365,322 @@ impl<'a> Solver<'a> {
 
                     applicant_weekly_total[applicant_index as usize] += session_length;
                     applicant_overall_total[applicant_index as usize] += session_length;
+                    if self.checks_days[applicant_index as usize] {
+                        applicant_sessions_this_week[applicant_index as usize].push(session_index);
+                    }
                 }
 
                 let num_tutors = allocation.assigned.len();
+                let max_tutors = max_tutors(session);
+                if num_tutors > max_tutors {
+                    return None;
+                }
+
                 if num_tutors > 0 && session.min_allocation.is_none() {
-                    min_size_this_week = min(min_size_this_week, num_tutors);
-                    max_size_this_week = max(max_size_this_week, num_tutors);
+                    let fill = num_tutors as f32 / max_tutors as f32;
+                    min_fill_this_week = min_fill_this_week.min(fill);
+                    max_fill_this_week = max_fill_this_week.max(fill);
+                }
+
+                if num_tutors > 0 {
+                    if self.new_tutors_alone(allocation) {
+                        return None;
+                    }
+
+                    for pairing in &self.pairings {
+                        let (first, second) = pairing.applicants;
+                        let first_assigned = allocation.assigned.contains(&first);
+                        let second_assigned = allocation.assigned.contains(&second);
+
+                        match (pairing.kind, pairing.weight) {
+                            // one of them is working without the other
+                            (PairingKind::PreferTogether, Some(weight))
+                                if first_assigned != second_assigned =>
+                            {
+                                breakdown.pairings += weight
+                            }
+                            (PairingKind::NeverTogether, weight)
+                                if first_assigned && second_assigned =>
+                            {
+                                breakdown.pairings += weight?
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    for requirement in &self.requirements[session_index] {
+                        let num_qualified = allocation
+                            .assigned
+                            .iter()
+                            .filter(|&&applicant| requirement.qualified[applicant as usize])
+                            .count();
+                        if num_qualified < requirement.at_least {
+                            if requirement.hard {
+                                return None;
+                            }
+                            breakdown.requirements +=
+                                100 * ((requirement.at_least - num_qualified) as Cost);
+                        }
+                    }
+                }
+
+                if let Some(min_if_open) = session.min_if_open {
+                    let min_if_open = min_if_open as usize;
+                    if num_tutors > 0 && num_tutors < min_if_open {
+                        breakdown.understaffed += 50 * ((min_if_open - num_tutors) as Cost);
+                    }
                 }
 
                 if let Some(min_allocation) = session.min_allocation {
                     let min_allocation = min_allocation as usize;
                     if num_tutors < min_allocation {
-                        total_cost += 50 * ((min_allocation - num_tutors) as Cost);
+                        breakdown.understaffed += 50 * ((min_allocation - num_tutors) as Cost);
                     }
                 }
             }
 
-            for (applicant_total, applicant) in applicant_weekly_total.iter().zip(self.applicants) {
-                if *applicant_total > applicant.max_hours_per_week {
+            for (applicant_index, (applicant_total, applicant)) in applicant_weekly_total
+                .iter()
+                .zip(self.applicants)
+                .enumerate()
+            {
+                if *applicant_total > hours_to_minutes(applicant.max_hours_per_week) {
                     return None;
                 }
 
+                if self.checks_days[applicant_index] {
+                    breakdown.days += self.eval_days(
+                        applicant_index,
+                        &mut applicant_sessions_this_week[applicant_index],
+                    )?;
+                }
+
+                if self.options.hours_variance_cost > 0 && week.available[applicant_index] {
+                    let hours = minutes_to_hours(*applicant_total);
+                    applicant_hours_sum[applicant_index] += hours;
+                    applicant_hours_squared_sum[applicant_index] += hours * hours;
+                }
+
                 if let Some(min_hours) = applicant.min_hours_per_week {
-                    if *applicant_total < min_hours {
-                        total_cost +=
-                            20 * (((min_hours - *applicant_total) as f32).powf(1.5) as Cost);
+                    let min_minutes = hours_to_minutes(min_hours);
+                    if *applicant_total < min_minutes {
+                        breakdown.tutor_hours += 20
+                            * (minutes_to_hours(min_minutes - *applicant_total).powf(1.5) as Cost);
                     }
                 }
             }
 
-            if effective_hours_this_week < week.desired_total_hours {
-                total_cost +=
-                    20 * ((week.desired_total_hours - effective_hours_this_week) as Cost).pow(2);
+            if effective_minutes_this_week < week.desired_total_minutes {
+                let shortfall =
+                    minutes_to_hours(week.desired_total_minutes - effective_minutes_this_week);
+                breakdown.desired_hours += (20.0 * shortfall.powi(2)) as Cost;
             } else {
-                let diff = (effective_hours_this_week - week.desired_total_hours) as Cost;
-                total_cost += 200 * diff;
+                let diff = (effective_minutes_this_week - week.desired_total_minutes) as Cost;
+                breakdown.desired_hours += 200 * diff / 60;
             }
 
-            if max_size_this_week > min_size_this_week + 2 {
-                total_cost += 50 * ((max_size_this_week - min_size_this_week) as Cost);
+            // a spread of more than two tutors in a session of five
+            let fill_spread = max_fill_this_week - min_fill_this_week;
+            if fill_spread > 0.4 + f32::EPSILON {
+                breakdown.fill_spread += (250.0 * fill_spread) as Cost;
             }
         }
 
-        total_cost += applicant_overall_total
-            .into_iter()
-            .map(|hours| (if hours > 6 { 0 } else { (6 - hours) * 10 }) as Cost)
+        breakdown.tutor_hours += applicant_overall_total
+            .iter()
+            .map(|&minutes| {
+                (if minutes > 360 {
+                    0
+                } else {
+                    (360 - minutes) * 10 / 60
+                }) as Cost
+            })
             .sum::<Cost>();
 
+        // hours spread evenly over the weeks a tutor can work
+        if self.options.hours_variance_cost > 0 {
+            for applicant_index in 0..self.applicants.len() {
+                let num_weeks = self
+                    .weeks
+                    .iter()
+                    .filter(|week| week.available[applicant_index])
+                    .count();
+                if self.bursty[applicant_index] || num_weeks < 2 {
+                    continue;
+                }
+
+                let mean = applicant_hours_sum[applicant_index] / num_weeks as f32;
+                let variance =
+                    applicant_hours_squared_sum[applicant_index] / num_weeks as f32 - mean * mean;
+                breakdown.hours_variance +=
+                    (self.options.hours_variance_cost as f32 * variance.max(0.0)) as Cost;
+            }
+        }
+
+        // the same tutors in a slot every week it runs
+        if self.options.slot_change_cost > 0 {
+            for slot in &self.slots {
+                let staffed = slot
+                    .iter()
+                    .map(|&session_index| &allocations[session_index].assigned)
+                    .filter(|assigned| !assigned.is_empty());
+                for (before, after) in staffed.tuple_windows() {
+                    let num_left = before.iter().filter(|&id| !after.contains(id)).count();
+                    let num_joined = after.iter().filter(|&id| !before.contains(id)).count();
+                    breakdown.slot_changes +=
+                        self.options.slot_change_cost * (num_left + num_joined) as Cost;
+                }
+            }
+        }
+
         // TOOD: disincentive not giving many hours to tutors who requested many
 
-        Some(total_cost)
+        Some(breakdown)
+    }
+
+    // new tutors always need someone experienced with them
+    fn new_tutors_alone(&self, allocation: &SessionAllocation) -> bool {
+        !allocation.assigned.is_empty()
+            && allocation
+                .assigned
+                .iter()
+                .all(|&applicant| self.new_tutors[applicant as usize])
+    }
+
+    // Tutors in any of a slot's sessions, who have it for the whole term
+    fn slot_tutors(&self, slot: usize, allocations: &[SessionAllocation]) -> Vec<ApplicantId> {
+        self.slots[slot]
+            .iter()
+            .flat_map(|&session_index| allocations[session_index].assigned.iter().copied())
+            .unique()
+            .collect()
+    }
+
+    // Whole-term mode: a tutor takes every week of a slot they can make, or none of them
+    fn mutate_slot_allocation(&self, allocations: &mut [SessionAllocation]) -> Option<Mutation> {
+        let slot = fastrand::usize(..self.slots.len());
+        let slot_tutors = self.slot_tutors(slot, allocations);
+
+        match fastrand::u8(0..=1) {
+            0 => {
+                let can_make = |applicant: usize, session_index: usize| {
+                    self.applicants[applicant].availabilities[session_index]
+                        != Availability::Impossible
+                };
+
+                // someone new who can make some weeks, and there's room for them in all of those
+                let all_possible_applicants = (0..self.applicants.len())
+                    .filter(|&applicant| {
+                        !slot_tutors.contains(&(applicant as _))
+                            && self.slots[slot]
+                                .iter()
+                                .any(|&index| can_make(applicant, index))
+                            && self.slots[slot].iter().all(|&index| {
+                                !can_make(applicant, index)
+                                    || allocations[index].assigned.len()
+                                        < max_tutors(&self.sessions[index])
+                            })
+                    })
+                    .collect::<Vec<_>>();
+
+                if all_possible_applicants.is_empty() {
+                    return None;
+                }
+
+                let applicant =
+                    all_possible_applicants[fastrand::usize(0..all_possible_applicants.len())];
+                for &session_index in &self.slots[slot] {
+                    if can_make(applicant, session_index) {
+                        allocations[session_index].assigned.push(applicant as _);
+                    }
+                }
+
+                Some(Mutation::AddToSlot {
+                    slot,
+                    applicant: applicant as _,
+                })
+            }
+            1 => {
+                if slot_tutors.is_empty() {
+                    return None;
+                }
+
+                let applicant = slot_tutors[fastrand::usize(0..slot_tutors.len())];
+                for &session_index in &self.slots[slot] {
+                    allocations[session_index]
+                        .assigned
+                        .retain(|&mut assigned| assigned != applicant);
+                }
+
+                Some(Mutation::RemoveFromSlot { slot, applicant })
+            }
+            _ => panic!(),
+        }
+    }
+
+    // The second pass of whole-term mode: the cheapest substitute for each week a slot's tutor
+    // can't make
+    fn fill_gaps(&self, allocations: &mut [SessionAllocation]) -> Vec<Gap> {
+        let mut gaps = vec![];
+
+        for slot in 0..self.slots.len() {
+            let slot_tutors = self.slot_tutors(slot, allocations);
+
+            for &session_index in &self.slots[slot] {
+                for &absent in &slot_tutors {
+                    if allocations[session_index].assigned.contains(&absent) {
+                        continue;
+                    }
+
+                    let candidates = (0..self.applicants.len() as ApplicantId)
+                        .filter(|&candidate| {
+                            self.applicants[candidate as usize].availabilities[session_index]
+                                != Availability::Impossible
+                                && !allocations[session_index].assigned.contains(&candidate)
+                        })
+                        .collect::<Vec<_>>();
+                    let substitute = candidates
+                        .into_iter()
+                        .filter_map(|candidate| {
+                            allocations[session_index].assigned.push(candidate);
+                            let cost = self.eval_allocation(allocations);
+                            allocations[session_index].assigned.pop();
+                            Some((cost?, candidate))
+                        })
+                        .min()
+                        .map(|(_, candidate)| candidate);
+
+                    if let Some(substitute) = substitute {
+                        allocations[session_index].assigned.push(substitute);
+                    }
+
+                    gaps.push(Gap {
+                        session: session_index,
+                        absent,
+                        substitute,
+                    });
+                }
+            }
+        }
+
+        gaps
     }
 
     fn mutate_allocation(&self, allocations: &mut [SessionAllocation]) -> Option<Mutation> {
+        if self.options.whole_term {
+            return self.mutate_slot_allocation(allocations);
+        }
+
         let session_index = fastrand::usize(..allocations.len());
 
         let action = fastrand::u8(0..=1);
 
         let assigned = &mut allocations[session_index].assigned;
+        let max_tutors = max_tutors(&self.sessions[session_index]);
 
         match action {
             0 => {
-                if assigned.len() == MAX_TUTORS_PER_SESSION {
+                if assigned.len() >= max_tutors {
                     // full!
                     return None;
                 }
@@ -176,7 +720,20 @@ impl<'a> Solver<'a> {
                     return None;
                 }
 
-                let applicant_index = fastrand::usize(0..assigned.len());
+                // don't leave new tutors on their own by removing the last experienced one
+                let num_experienced = assigned
+                    .iter()
+                    .filter(|&&applicant| !self.new_tutors[applicant as usize])
+                    .count();
+                let removable = (0..assigned.len())
+                    .filter(|&index| {
+                        self.new_tutors[assigned[index] as usize]
+                            || num_experienced > 1
+                            || num_experienced == assigned.len()
+                    })
+                    .collect::<Vec<_>>();
+
+                let applicant_index = removable[fastrand::usize(0..removable.len())];
                 let applicant = assigned[applicant_index];
                 assigned.remove(applicant_index);
 
@@ -194,17 +751,28 @@ fn solve(
     applicants: &[Applicant],
     sessions: &[Session],
     desired_hours: &[(WeekNum, HourCount)],
-    quick: bool,
-) -> (Cost, Vec<SessionAllocation>) {
+    pairings: &[Pairing],
+    options: &SolverOptions,
+) -> (CostBreakdown, Vec<SessionAllocation>, Vec<Gap>) {
     let weeks = desired_hours
         .iter()
         .map(|(week_num, desired_total)| Week {
-            desired_total_hours: *desired_total,
+            desired_total_minutes: hours_to_minutes(*desired_total),
             session_indexes: sessions
                 .iter()
                 .enumerate()
                 .filter_map(|(idx, session)| (session.week == *week_num).then_some(idx))
                 .collect(),
+            available: applicants
+                .iter()
+                .map(|applicant| {
+                    sessions.iter().zip(&applicant.availabilities).any(
+                        |(session, &availability)| {
+                            session.week == *week_num && availability != Availability::Impossible
+                        },
+                    )
+                })
+                .collect(),
         })
         .collect::<Vec<_>>();
 
@@ -217,10 +785,102 @@ fn solve(
                 .len()
     );
 
+    let requirements = sessions
+        .iter()
+        .map(|session| {
+            session
+                .requirements
+                .iter()
+                .map(|requirement| SkillRequirement {
+                    at_least: requirement.at_least as usize,
+                    hard: requirement.hard,
+                    qualified: applicants
+                        .iter()
+                        .map(|applicant| applicant.tags.contains(&requirement.tag))
+                        .collect(),
+                })
+                .collect()
+        })
+        .collect();
+
+    let has_tag = |applicant: &Applicant, tag: &str| applicant.tags.iter().any(|t| t == tag);
+    let new_tutors = applicants
+        .iter()
+        .map(|applicant| has_tag(applicant, NEW_TUTOR_TAG))
+        .collect();
+    let bursty = applicants
+        .iter()
+        .map(|applicant| has_tag(applicant, BURSTY_TUTOR_TAG))
+        .collect();
+
+    let applicant_id = |zid: &str| {
+        applicants
+            .iter()
+            .position(|applicant| applicant.zid == zid)
+            .map(|index| index as ApplicantId)
+    };
+    // pairings with someone not teaching this course don't matter
+    let pairings = pairings
+        .iter()
+        .filter_map(|pairing| {
+            Some(PairingCost {
+                applicants: (
+                    applicant_id(&pairing.zids.0)?,
+                    applicant_id(&pairing.zids.1)?,
+                ),
+                kind: pairing.kind,
+                weight: pairing.weight.map(|weight| weight as Cost),
+            })
+        })
+        .collect();
+
+    let daily_limits = applicants
+        .iter()
+        .map(|applicant| DailyLimits {
+            max_minutes_per_day: applicant
+                .max_hours_per_day
+                .or(options.max_hours_per_day)
+                .map(hours_to_minutes),
+            max_consecutive_minutes: applicant
+                .max_consecutive_hours
+                .or(options.max_consecutive_hours)
+                .map(hours_to_minutes),
+        })
+        .collect::<Vec<_>>();
+    let checks_days = daily_limits
+        .iter()
+        .map(|limits| {
+            limits.max_minutes_per_day.is_some()
+                || limits.max_consecutive_minutes.is_some()
+                || options.travel_gap.is_some()
+                || options.campus_day_cost > 0
+                || options.campus_idle_hour_cost > 0
+                || options.max_campus_days.is_some()
+        })
+        .collect();
+
     let solver = Solver {
         sessions,
         applicants,
         weeks,
+        requirements,
+        new_tutors,
+        bursty,
+        pairings,
+        daily_limits,
+        checks_days,
+        slots: recurring_slots(sessions)
+            .into_iter()
+            .map(|(_, session_indexes)| session_indexes)
+            .collect(),
+        options,
+        scratch: RefCell::new(EvalScratch {
+            overall_total: vec![0; applicants.len()],
+            weekly_total: vec![0; applicants.len()],
+            hours_sum: vec![0.0; applicants.len()],
+            hours_squared_sum: vec![0.0; applicants.len()],
+            sessions_this_week: vec![vec![]; applicants.len()],
+        }),
     };
 
     let mut allocation = (0..sessions.len())
@@ -233,7 +893,7 @@ fn solve(
     // println!("initial cost: {old_cost}");
     let mut old_allocation = allocation.clone();
 
-    let total_steps = if !quick { 8000000 } else { 30000 };
+    let total_steps = if !options.quick { 8000000 } else { 30000 };
     let temp_multiplier = 1.5;
     // let temp_multiplier = 1.0;
 
@@ -278,7 +938,20 @@ fn solve(
         }
     }
 
-    (old_cost, old_allocation)
+    let gaps = if options.whole_term {
+        solver.fill_gaps(&mut old_allocation)
+    } else {
+        vec![]
+    };
+
+    let mut breakdown = solver
+        .eval_allocation_breakdown(&old_allocation)
+        .expect("the solution should meet every hard constraint");
+    breakdown.new_tutors_alone = old_allocation
+        .iter()
+        .filter(|allocation| solver.new_tutors_alone(allocation))
+        .count();
+    (breakdown, old_allocation, gaps)
 }
 
 pub fn solve_many_times(
@@ -287,8 +960,9 @@ pub fn solve_many_times(
     applicants: &[Applicant],
     sessions: &[Session],
     desired_hours: &[(WeekNum, HourCount)],
-    quick: bool,
-) -> (Vec<SolvedSession>, Seed) {
+    pairings: &[Pairing],
+    options: &SolverOptions,
+) -> (Vec<SolvedSession>, Vec<Substitution>, Seed) {
     let applicants = &applicants
         .iter()
         .filter(|applicant| applicant.course == course)
@@ -301,7 +975,9 @@ pub fn solve_many_times(
             // .into_iter()
             .min_by_key(|&seed| {
                 fastrand::seed(*seed);
-                let (cost, _) = solve(applicants, sessions, desired_hours, quick);
+                let (breakdown, _, _) =
+                    solve(applicants, sessions, desired_hours, pairings, options);
+                let cost = breakdown.total();
                 println!("seed = {seed}, cost = {cost}");
                 cost
             })
@@ -314,9 +990,13 @@ pub fn solve_many_times(
     };
 
     fastrand::seed(best_seed);
-    let (best_cost, solution) = solve(applicants, sessions, desired_hours, quick);
+    let (breakdown, solution, gaps) = solve(applicants, sessions, desired_hours, pairings, options);
+    let best_cost = breakdown.total();
 
     println!("best_cost = {best_cost:?} with seed {best_seed:?} (for {course:?})");
+    for line in breakdown.lines() {
+        println!("    {line}");
+    }
     // println!("solution = {solution:?}");
 
     (
@@ -332,6 +1012,15 @@ pub fn solve_many_times(
                     .collect(),
             })
             .collect::<Vec<_>>(),
+        gaps.into_iter()
+            .map(|gap| Substitution {
+                session_key: sessions[gap.session].key.clone(),
+                absent_zid: applicants[gap.absent as usize].zid.clone(),
+                substitute_zid: gap
+                    .substitute
+                    .map(|substitute| applicants[substitute as usize].zid.clone()),
+            })
+            .collect(),
         best_seed,
     )
 }
@@ -51,6 +51,16 @@ impl<'a> Row<'a> {
         &self.tsv.rows[self.row_index][index]
     }
 
+    // None if the TSV has no such column
+    pub fn try_get(&self, column: &str) -> Option<&'a str> {
+        let index = self
+            .tsv
+            .headers
+            .iter()
+            .position(|header| header == column)?;
+        Some(&self.tsv.rows[self.row_index][index])
+    }
+
     pub fn all_fields(&self) -> &[String] {
         &self.tsv.rows[self.row_index]
     }
@@ -61,6 +71,10 @@ impl Tsv {
         self.rows.len()
     }
 
+    pub fn has_column(&self, column: &str) -> bool {
+        self.headers.iter().any(|header| header == column)
+    }
+
     pub fn from_string(contents: &str) -> Self {
         let mut lines = contents.lines();
         let headers = lines.next().unwrap();
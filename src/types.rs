@@ -1,4 +1,8 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{calendar::Date, solver::Cost};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Day {
@@ -8,28 +12,26 @@ pub enum Day {
     Thu,
     Fri,
     Sat,
+    Sun,
 }
 
 impl FromStr for Day {
     type Err = String;
 
+    // Any case, and any abbreviation of at least two letters ("Th", "Tues", "SUN"), plus "Weds"
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use Day::*;
-        Ok(match s {
-            "mon" => Mon,
-            "tue" => Tue,
-            "wed" => Wed,
-            "thu" => Thu,
-            "fri" => Fri,
-            "sat" => Sat,
-            "Monday" => Mon,
-            "Tuesday" => Tue,
-            "Wednesday" => Wed,
-            "Thursday" => Thu,
-            "Friday" => Fri,
-            "Saturday" => Sat,
-            _ => return Err(format!("bad day {s:?}")),
-        })
+        let lowercase = s.trim().to_lowercase();
+        if lowercase == "weds" {
+            return Ok(Wed);
+        }
+
+        [Mon, Tue, Wed, Thu, Fri, Sat, Sun]
+            .into_iter()
+            .find(|day| {
+                lowercase.len() >= 2 && day.long_name().to_lowercase().starts_with(&lowercase)
+            })
+            .ok_or_else(|| format!("bad day {s:?}"))
     }
 }
 
@@ -42,6 +44,7 @@ impl Day {
             Day::Thu => "Thursday",
             Day::Fri => "Friday",
             Day::Sat => "Saturday",
+            Day::Sun => "Sunday",
         }
     }
 
@@ -53,6 +56,7 @@ impl Day {
             Day::Thu => "Thu",
             Day::Fri => "Fri",
             Day::Sat => "Sat",
+            Day::Sun => "Sun",
         }
     }
 }
@@ -64,6 +68,8 @@ pub struct WeekNum(pub u8);
 pub enum Venue {
     FaceToFace,
     Online,
+    /// Tutors are in the room, and also watch a stream
+    Hybrid,
 }
 
 impl FromStr for Venue {
@@ -73,41 +79,185 @@ impl FromStr for Venue {
         Ok(match s {
             "f2f" => Venue::FaceToFace,
             "online" => Venue::Online,
+            "hybrid" => Venue::Hybrid,
             _ => return Err(()),
         })
     }
 }
 
+impl Venue {
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Venue::FaceToFace => "f2f",
+            Venue::Online => "online",
+            Venue::Hybrid => "hybrid",
+        }
+    }
+
+    // whether tutors have to be there in person
+    pub fn is_in_person(self) -> bool {
+        match self {
+            Venue::FaceToFace | Venue::Hybrid => true,
+            Venue::Online => false,
+        }
+    }
+
+    fn has_stream(self) -> bool {
+        match self {
+            Venue::Online | Venue::Hybrid => true,
+            Venue::FaceToFace => false,
+        }
+    }
+}
+
+/// A venue, optionally with the online platform it streams on, e.g. "f2f", "online",
+/// "online:zoom" or "hybrid:teams"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VenueSpec {
+    pub venue: Venue,
+    pub platform: Option<String>,
+}
+
+impl FromStr for VenueSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (venue, platform) = match s.split_once(':') {
+            Some((venue, platform)) => (venue, Some(platform.to_string())),
+            None => (s, None),
+        };
+        let venue: Venue = venue.parse().map_err(|()| format!("bad venue {s:?}"))?;
+
+        if platform.is_some() && !venue.has_stream() {
+            return Err(format!("{venue:?} venue {s:?} can't have a platform"));
+        }
+
+        Ok(VenueSpec { venue, platform })
+    }
+}
+
+impl VenueSpec {
+    // "online" matches online sessions on any platform
+    pub fn matches(&self, session: &Session) -> bool {
+        session.venue == self.venue
+            && self
+                .platform
+                .as_ref()
+                .is_none_or(|platform| session.platform.as_ref() == Some(platform))
+    }
+}
+
+/// e.g. at least one "experienced" tutor, whenever the session runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagRequirement {
+    pub tag: String,
+    #[serde(default = "TagRequirement::default_at_least")]
+    pub at_least: u16,
+    /// Hard requirements can't be broken; soft ones just cost
+    #[serde(default)]
+    pub hard: bool,
+}
+
+impl TagRequirement {
+    fn default_at_least() -> u16 {
+        1
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairingKind {
+    PreferTogether,
+    NeverTogether,
+}
+
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    pub zids: (String, String),
+    pub kind: PairingKind,
+    /// None for a hard "never together"
+    pub weight: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Room {
+    pub id: String,
+    /// Most tutors that fit (alongside the students)
+    pub capacity: u16,
+    pub campus: String,
+    /// e.g. "accessible", "hearing loop"
+    pub flags: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Session {
-    pub id: usize,
+    /// Stays the same when other sessions are added or removed
+    pub key: String,
+    /// Which of several identical parallel sessions this is, from 1 (the second and later ones'
+    /// keys end in "#2", "#3"...)
+    pub copy: u16,
     pub day: Day,
     pub week: WeekNum,
+    /// Only known when there's a term calendar
+    pub date: Option<Date>,
     pub venue: Venue,
-    pub time_24hr: u8,
-    pub length_hours: u8,
+    /// Which online platform (and so link pool) an online or hybrid session uses
+    pub platform: Option<String>,
+    /// minutes after midnight
+    pub start_minutes: u16,
+    pub length_minutes: u16,
     pub location: String,
+    /// In-person sessions' rooms, when there's a rooms.tsv
+    pub room: Option<Room>,
+    /// Room flags this session needs
+    pub room_flags: Vec<String>,
     pub min_allocation: Option<u16>,
+    pub max_allocation: Option<u16>,
+    /// Fewest tutors worth running the session with, if it runs at all
+    pub min_if_open: Option<u16>,
+    pub capacity: Option<u16>,
+    /// Tutor tags the session needs
+    pub requirements: Vec<TagRequirement>,
+    pub tags: Vec<String>,
+    pub notes: String,
+}
+
+impl Session {
+    pub fn end_minutes(&self) -> u16 {
+        self.start_minutes + self.length_minutes
+    }
+
+    // The key without a parallel copy's "#2", so the same for every copy
+    pub fn definition_key(&self) -> &str {
+        match self.copy {
+            1 => &self.key,
+            copy => self
+                .key
+                .strip_suffix(&format!("#{copy}"))
+                .unwrap_or(&self.key),
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
 pub enum Course {
     #[clap(name = "COMP1511")]
+    #[serde(rename = "COMP1511")]
     Comp1511,
     #[clap(name = "COMP1521")]
+    #[serde(rename = "COMP1521")]
     Comp1521,
     #[clap(name = "COMP2521")]
+    #[serde(rename = "COMP2521")]
     Comp2521,
 }
 
-impl ToString for Course {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Course {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             Course::Comp1511 => "COMP1511",
             Course::Comp1521 => "COMP1521",
             Course::Comp2521 => "COMP2521",
-        }
-        .into()
+        })
     }
 }
 
@@ -134,12 +284,42 @@ pub enum Availability {
 
 #[derive(Debug, Clone)]
 pub struct Applicant {
-    pub id: u32,
     pub email: String,
     pub name: String,
     pub zid: String,
     pub course: Course,
     pub max_hours_per_week: u16,
     pub availabilities: Vec<Availability>,
+    /// Extra cost of giving this tutor each session, e.g. for rushing between campuses
+    pub extra_costs: Vec<Cost>,
     pub min_hours_per_week: Option<u16>,
+    /// None for the solver's default
+    pub max_hours_per_day: Option<u16>,
+    /// Longest unbroken run of back-to-back sessions; None for the solver's default
+    pub max_consecutive_hours: Option<u16>,
+    /// From roster.tsv, e.g. "experienced", "new" or "lead"
+    pub tags: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_in_any_case_and_abbreviation() {
+        assert_eq!("Sun".parse(), Ok(Day::Sun));
+        assert_eq!("MON".parse(), Ok(Day::Mon));
+        assert_eq!("Thurs".parse(), Ok(Day::Thu));
+        assert_eq!("Tu".parse(), Ok(Day::Tue));
+        assert_eq!("weds".parse(), Ok(Day::Wed));
+        assert_eq!(" friday ".parse(), Ok(Day::Fri));
+    }
+
+    #[test]
+    fn ambiguous_or_unknown_days() {
+        assert_eq!("T".parse::<Day>(), Err(r#"bad day "T""#.to_string()));
+        assert!("S".parse::<Day>().is_err());
+        assert!("".parse::<Day>().is_err());
+        assert!("Mondays".parse::<Day>().is_err());
+    }
 }
@@ -0,0 +1,28 @@
+use crate::{read_roster::NEW_TUTOR_TAG, solution_output::SolvedSession, types::Applicant};
+
+fn is_new(applicant: &Applicant) -> bool {
+    applicant.tags.iter().any(|tag| tag == NEW_TUTOR_TAG)
+}
+
+// Hard constraints broken by a finished solution, one line each
+pub fn find_violations(solution: &[SolvedSession]) -> Vec<String> {
+    let mut violations = vec![];
+
+    for solved in solution {
+        let applicants = &solved.applicants;
+
+        if !applicants.is_empty() && applicants.iter().all(is_new) {
+            violations.push(format!(
+                "{}: new tutors with no one experienced ({})",
+                solved.session.key,
+                applicants
+                    .iter()
+                    .map(|applicant| applicant.zid.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    violations
+}